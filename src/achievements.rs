@@ -0,0 +1,233 @@
+use scoring::{Tier, COMBO_MAX_MULTIPLIER};
+
+/// ********************************************************************
+/// Achievements
+///
+/// The game tells the tracker what happens as a `GameEvent`, from the
+/// same places the score, the dead minions and the fire pits are looked
+/// after. The tracker keeps whatever counts it needs for the run and
+/// hands back any achievement the event earned. Whether it's new is up
+/// to the profile, which keeps every achievement ever earned.
+/// ********************************************************************
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstHighFive,
+    Perfectionist,
+    Arsonist,
+    Pacifist,
+    ComboMaster,
+    SocialButterfly,
+    LordOfMany,
+    Spotless,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 8] = [
+    Achievement::FirstHighFive,
+    Achievement::Perfectionist,
+    Achievement::Arsonist,
+    Achievement::Pacifist,
+    Achievement::ComboMaster,
+    Achievement::SocialButterfly,
+    Achievement::LordOfMany,
+    Achievement::Spotless,
+];
+
+/// Perfect high fives in a row for `Perfectionist`
+const PERFECT_STREAK: u32 = 10;
+/// Bodies burned in one run for `Arsonist`
+const BODIES_BURNED: u32 = 5;
+/// Friends in one run without a death for `Pacifist`
+const PACIFIST_FRIENDS: u32 = 5;
+/// Friends in one run for `SocialButterfly`
+const RUN_FRIENDS: u32 = 25;
+/// Friends over every run for `LordOfMany`
+const TOTAL_FRIENDS: u32 = 100;
+/// Friends in one run with a clean floor at the end for `Spotless`
+const SPOTLESS_FRIENDS: u32 = 10;
+
+impl Achievement {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Achievement::FirstHighFive => "First High Five",
+            Achievement::Perfectionist => "Perfectionist",
+            Achievement::Arsonist => "Arsonist",
+            Achievement::Pacifist => "Pacifist",
+            Achievement::ComboMaster => "Combo Master",
+            Achievement::SocialButterfly => "Social Butterfly",
+            Achievement::LordOfMany => "Lord Of Many",
+            Achievement::Spotless => "Spotless",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Achievement::FirstHighFive => "Land a high five",
+            Achievement::Perfectionist => "Ten perfect high fives in a row",
+            Achievement::Arsonist => "Burn five bodies in one run",
+            Achievement::Pacifist => "Finish a run with five friends and no dead minions",
+            Achievement::ComboMaster => "Reach the top combo multiplier",
+            Achievement::SocialButterfly => "Make 25 friends in one run",
+            Achievement::LordOfMany => "Make 100 friends over every run",
+            Achievement::Spotless => "Finish a run with ten friends and nothing left to find",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    /// `friends` counts this run's, `total_friends` the ones from
+    /// earlier runs
+    HighFive { tier: Tier, multiplier: u32, friends: u32, total_friends: u32 },
+    /// A missed high five, whether or not the minion died of it
+    Miss,
+    BodyBurned,
+    /// `deaths_possible` is false in a mode where minions can't die, where
+    /// no dead minions proves nothing
+    RunFinished { friends: u32, kills: u32, deaths_possible: bool, clean: bool },
+}
+
+/// Counts kept over one run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AchievementTracker {
+    perfect_streak: u32,
+    burned: u32,
+}
+
+impl AchievementTracker {
+    /// Achievements earned by an event, new or not
+    pub fn handle(&mut self, event: GameEvent) -> Vec<Achievement> {
+        let mut earned = vec![];
+        match event {
+            GameEvent::HighFive { tier, multiplier, friends, total_friends } => {
+                earned.push(Achievement::FirstHighFive);
+                if tier == Tier::Perfect {
+                    self.perfect_streak += 1;
+                }
+                else {
+                    self.perfect_streak = 0;
+                }
+                if self.perfect_streak >= PERFECT_STREAK {
+                    earned.push(Achievement::Perfectionist);
+                }
+                if multiplier >= COMBO_MAX_MULTIPLIER {
+                    earned.push(Achievement::ComboMaster);
+                }
+                if friends >= RUN_FRIENDS {
+                    earned.push(Achievement::SocialButterfly);
+                }
+                if friends + total_friends >= TOTAL_FRIENDS {
+                    earned.push(Achievement::LordOfMany);
+                }
+            }
+            GameEvent::Miss => {
+                self.perfect_streak = 0;
+            }
+            GameEvent::BodyBurned => {
+                self.burned += 1;
+                if self.burned >= BODIES_BURNED {
+                    earned.push(Achievement::Arsonist);
+                }
+            }
+            GameEvent::RunFinished { friends, kills, deaths_possible, clean } => {
+                if deaths_possible && kills == 0 && friends >= PACIFIST_FRIENDS {
+                    earned.push(Achievement::Pacifist);
+                }
+                if clean && friends >= SPOTLESS_FRIENDS {
+                    earned.push(Achievement::Spotless);
+                }
+            }
+        }
+        earned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high_five(tier: Tier) -> GameEvent {
+        GameEvent::HighFive { tier: tier, multiplier: 1, friends: 1, total_friends: 0 }
+    }
+
+    fn run_finished(friends: u32, kills: u32) -> GameEvent {
+        GameEvent::RunFinished { friends: friends, kills: kills, deaths_possible: true, clean: false }
+    }
+
+    /// Perfect high fives until just short of `Perfectionist`
+    fn nearly_perfect(tracker: &mut AchievementTracker) {
+        for _ in 0..PERFECT_STREAK - 1 {
+            assert!(!tracker.handle(high_five(Tier::Perfect)).contains(&Achievement::Perfectionist));
+        }
+    }
+
+    #[test]
+    fn perfect_streak_earns_perfectionist() {
+        let mut tracker = AchievementTracker::default();
+        nearly_perfect(&mut tracker);
+        assert!(tracker.handle(high_five(Tier::Perfect)).contains(&Achievement::Perfectionist));
+    }
+
+    #[test]
+    fn perfect_streak_resets_on_a_lesser_tier() {
+        let mut tracker = AchievementTracker::default();
+        nearly_perfect(&mut tracker);
+        tracker.handle(high_five(Tier::Great));
+        nearly_perfect(&mut tracker);
+        assert!(tracker.handle(high_five(Tier::Perfect)).contains(&Achievement::Perfectionist));
+    }
+
+    #[test]
+    fn perfect_streak_resets_on_a_miss() {
+        let mut tracker = AchievementTracker::default();
+        nearly_perfect(&mut tracker);
+        assert!(tracker.handle(GameEvent::Miss).is_empty());
+        assert!(!tracker.handle(high_five(Tier::Perfect)).contains(&Achievement::Perfectionist));
+    }
+
+    #[test]
+    fn arsonist_on_the_fifth_body() {
+        let mut tracker = AchievementTracker::default();
+        for _ in 0..BODIES_BURNED - 1 {
+            assert!(tracker.handle(GameEvent::BodyBurned).is_empty());
+        }
+        assert_eq!(tracker.handle(GameEvent::BodyBurned), vec![Achievement::Arsonist]);
+    }
+
+    #[test]
+    fn pacifist_needs_no_dead_minions() {
+        let mut tracker = AchievementTracker::default();
+        assert_eq!(tracker.handle(run_finished(PACIFIST_FRIENDS, 0)), vec![Achievement::Pacifist]);
+        assert!(tracker.handle(run_finished(PACIFIST_FRIENDS, 1)).is_empty());
+        assert!(tracker.handle(run_finished(PACIFIST_FRIENDS - 1, 0)).is_empty());
+    }
+
+    #[test]
+    fn pacifist_needs_a_mode_with_deaths() {
+        let mut tracker = AchievementTracker::default();
+        let event = GameEvent::RunFinished {
+            friends: PACIFIST_FRIENDS,
+            kills: 0,
+            deaths_possible: false,
+            clean: false,
+        };
+        assert!(tracker.handle(event).is_empty());
+    }
+
+    #[test]
+    fn lord_of_many_counts_earlier_runs() {
+        let mut tracker = AchievementTracker::default();
+        let lord_of_many = |tracker: &mut AchievementTracker, friends, total_friends| {
+            let event = GameEvent::HighFive {
+                tier: Tier::Good,
+                multiplier: 1,
+                friends: friends,
+                total_friends: total_friends,
+            };
+            tracker.handle(event).contains(&Achievement::LordOfMany)
+        };
+        assert!(!lord_of_many(&mut tracker, 10, TOTAL_FRIENDS - 11));
+        assert!(lord_of_many(&mut tracker, 10, TOTAL_FRIENDS - 10));
+        assert!(lord_of_many(&mut tracker, 1, TOTAL_FRIENDS - 1));
+    }
+}
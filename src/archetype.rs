@@ -0,0 +1,208 @@
+use ggez::graphics::Color;
+use rand::Rng;
+
+use difficulty::{Difficulty, lerp};
+
+/// ********************************************************************
+/// Minion Archetypes
+///
+/// Minions come in a few personalities, each high fiving differently.
+/// Everything is scaled from the current difficulty, so the difficulty
+/// curve still ramps every archetype up together. Each archetype rolls
+/// its goal ring from its own part of the curve's goal range, and is
+/// drawn as the plain minion in its own tint.
+/// ********************************************************************
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Archetype {
+    Regular,
+    /// Small goal ring and a quick ring, worth a bit more
+    Shy,
+    /// Slow ring and a forgiving window, but not much time for it
+    Enthusiastic,
+    /// Fast ring and a tight window, worth more time
+    Grumpy,
+    /// Rare, hardest of the lot and worth the most
+    Vip,
+    /// Slow to get going, then a tight window
+    Sleepy,
+    /// Very fast ring, but a fair window
+    Jester,
+}
+
+impl Default for Archetype {
+    fn default() -> Self {
+        Archetype::Regular
+    }
+}
+
+pub const ARCHETYPES: [Archetype; 7] = [
+    Archetype::Regular,
+    Archetype::Shy,
+    Archetype::Enthusiastic,
+    Archetype::Grumpy,
+    Archetype::Vip,
+    Archetype::Sleepy,
+    Archetype::Jester,
+];
+
+pub struct ArchetypeInfo {
+    pub name: &'static str,
+    pub tint: Color,
+    /// Relative chance of spawning
+    pub weight: u32,
+    pub ring_speed_scale: f32,
+    /// Part of the curve's goal range the goal ring is rolled from, 0
+    /// being the smallest goal ring and 1 the largest
+    pub goal_from: f32,
+    pub goal_to: f32,
+    pub hit_window_scale: f32,
+    /// Points for a high five
+    pub score: u32,
+    pub time_bonus_scale: f32,
+    /// Friends needed in total before it starts turning up, see `profile`
+    pub unlock_friends: u32,
+}
+
+impl Archetype {
+    pub fn info(&self) -> ArchetypeInfo {
+        match *self {
+            Archetype::Regular => ArchetypeInfo {
+                name: "Minion",
+                tint: Color::new(1.0, 1.0, 1.0, 1.0),
+                weight: 50,
+                ring_speed_scale: 1.0,
+                goal_from: 0.0,
+                goal_to: 1.0,
+                hit_window_scale: 1.0,
+                score: 1,
+                time_bonus_scale: 1.0,
+                unlock_friends: 0,
+            },
+            Archetype::Shy => ArchetypeInfo {
+                name: "Shy Minion",
+                tint: Color::new(0.7, 0.8, 1.0, 1.0),
+                weight: 20,
+                ring_speed_scale: 1.25,
+                goal_from: 0.0,
+                goal_to: 0.35,
+                hit_window_scale: 0.9,
+                score: 2,
+                time_bonus_scale: 1.0,
+                unlock_friends: 0,
+            },
+            Archetype::Enthusiastic => ArchetypeInfo {
+                name: "Enthusiastic Minion",
+                tint: Color::new(1.0, 1.0, 0.6, 1.0),
+                weight: 20,
+                ring_speed_scale: 0.8,
+                goal_from: 0.35,
+                goal_to: 1.0,
+                hit_window_scale: 1.3,
+                score: 1,
+                time_bonus_scale: 0.6,
+                unlock_friends: 0,
+            },
+            Archetype::Grumpy => ArchetypeInfo {
+                name: "Grumpy Minion",
+                tint: Color::new(1.0, 0.6, 0.6, 1.0),
+                weight: 8,
+                ring_speed_scale: 1.4,
+                goal_from: 0.0,
+                goal_to: 0.65,
+                hit_window_scale: 0.7,
+                score: 3,
+                time_bonus_scale: 1.5,
+                unlock_friends: 0,
+            },
+            Archetype::Vip => ArchetypeInfo {
+                name: "VIP Minion",
+                tint: Color::new(1.0, 0.85, 0.3, 1.0),
+                weight: 2,
+                ring_speed_scale: 1.6,
+                goal_from: 0.0,
+                goal_to: 0.5,
+                hit_window_scale: 0.6,
+                score: 5,
+                time_bonus_scale: 2.0,
+                unlock_friends: 0,
+            },
+            Archetype::Sleepy => ArchetypeInfo {
+                name: "Sleepy Minion",
+                tint: Color::new(0.8, 0.7, 1.0, 1.0),
+                weight: 15,
+                ring_speed_scale: 0.6,
+                goal_from: 0.2,
+                goal_to: 0.8,
+                hit_window_scale: 0.75,
+                score: 2,
+                time_bonus_scale: 1.2,
+                unlock_friends: 75,
+            },
+            Archetype::Jester => ArchetypeInfo {
+                name: "Jester Minion",
+                tint: Color::new(0.6, 1.0, 0.6, 1.0),
+                weight: 10,
+                ring_speed_scale: 1.8,
+                goal_from: 0.1,
+                goal_to: 0.9,
+                hit_window_scale: 1.1,
+                score: 3,
+                time_bonus_scale: 1.5,
+                unlock_friends: 200,
+            },
+        }
+    }
+
+    /// The current difficulty as it applies to high fiving this minion
+    pub fn apply(&self, base: Difficulty) -> Difficulty {
+        let info = self.info();
+        Difficulty {
+            ring_speed_min: base.ring_speed_min * info.ring_speed_scale,
+            ring_speed_max: base.ring_speed_max * info.ring_speed_scale,
+            goal_min: lerp(base.goal_min, base.goal_max, info.goal_from),
+            goal_max: lerp(base.goal_min, base.goal_max, info.goal_to),
+            hit_window: base.hit_window * info.hit_window_scale,
+            time_bonus: base.time_bonus * info.time_bonus_scale,
+            ..base
+        }
+    }
+}
+
+/// Picks an archetype from the weighted table, out of those unlocked by
+/// `total_friends`
+pub fn roll_archetype<R: Rng>(rng: &mut R, total_friends: u32) -> Archetype {
+    let unlocked: Vec<Archetype> = ARCHETYPES.iter().cloned()
+        .filter(|a| a.info().unlock_friends <= total_friends)
+        .collect();
+    let total: u32 = unlocked.iter().map(|a| a.info().weight).sum();
+    let mut pick = rng.gen_range(0, total);
+    for archetype in unlocked.iter() {
+        let weight = archetype.info().weight;
+        if pick < weight {
+            return *archetype;
+        }
+        pick -= weight;
+    }
+    Archetype::Regular
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use difficulty::DifficultyCurve;
+
+    #[test]
+    fn goal_range_follows_the_curve() {
+        let curve = DifficultyCurve::default();
+        let (early, late) = (curve.at(0), curve.at(50));
+        let regular = Archetype::Regular.apply(early);
+        assert_eq!((regular.goal_min, regular.goal_max), (early.goal_min, early.goal_max));
+        for archetype in ARCHETYPES.iter() {
+            let (a, b) = (archetype.apply(early), archetype.apply(late));
+            assert!(a.goal_min <= a.goal_max && b.goal_min <= b.goal_max);
+            assert!(a.goal_min >= early.goal_min && a.goal_max <= early.goal_max);
+            assert!(b.goal_max - b.goal_min < a.goal_max - a.goal_min);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ggez::{Context, GameResult, GameError};
+use rand::{StdRng, SeedableRng};
+use serde_json;
+
+/// ********************************************************************
+/// Daily Challenge
+///
+/// Everyone playing on the same (UTC) day gets the same seed. Rather
+/// than sharing one generator, which minion wandering would throw out of
+/// step, every spawn and every high five gets a generator of its own
+/// seeded from the day and how many came before it. That way the nth
+/// spawn and the nth ring are rolled from the same numbers for everyone.
+/// What comes of those numbers can still differ between players: a spawn
+/// point is tried again wherever a minion has wandered into the way, and
+/// the ring is scaled by the friends made so far and the minion's
+/// archetype. Mutators are switched off, so they can't make a
+/// difference.
+/// ********************************************************************
+
+pub const DAILY_FILE: &'static str = "/daily.json";
+
+/// Which kind of roll a generator is for, so spawns and rings don't
+/// share numbers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Spawn = 1,
+    Minigame = 2,
+}
+
+/// Days since 1970-01-01, UTC
+pub fn today() -> u32 {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    (secs / 86400) as u32
+}
+
+/// A day as year, month and day of the month
+pub fn civil_date(days: u32) -> (i32, u32, u32) {
+    // Howard Hinnant's days_from_civil, run backwards
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+    (year, month, day)
+}
+
+pub fn date_string(days: u32) -> String {
+    let (year, month, day) = civil_date(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn daily_seed(days: u32) -> u32 {
+    days.wrapping_mul(2654435761) ^ 0x4c4f5244
+}
+
+/// Generator for the `n`th roll of a stream
+pub fn seeded_rng(seed: u32, stream: Stream, n: u32) -> StdRng {
+    let key = [seed as usize, stream as usize, n as usize];
+    StdRng::from_seed(&key[..])
+}
+
+/// FNV-1a, just to keep result codes from being typed up by hand
+fn checksum(text: &str) -> u32 {
+    let mut hash: u32 = 2166136261;
+    for byte in text.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(16777619);
+    }
+    hash
+}
+
+/// Something like `LHF-20261019-S42-F12-7F3A`, to paste in chat
+pub fn result_code(days: u32, score: u32, friends: u32) -> String {
+    let (year, month, day) = civil_date(days);
+    let body = format!("LHF-{:04}{:02}{:02}-S{}-F{}", year, month, day, score, friends);
+    let check = checksum(&body) & 0xffff;
+    format!("{}-{:04X}", body, check)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub score: u32,
+    pub friends: u32,
+    pub code: String,
+}
+
+/// Best result of every day played, keyed by date. Kept in the profile;
+/// `DAILY_FILE` is only read to bring old results into the first one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyResults {
+    pub days: BTreeMap<String, DailyResult>,
+}
+
+impl DailyResults {
+    pub fn best(&self, days: u32) -> Option<&DailyResult> {
+        self.days.get(&date_string(days))
+    }
+
+    /// Keeps the result if it beats the day's best, returning whether it did
+    pub fn record(&mut self, days: u32, score: u32, friends: u32) -> bool {
+        let date = date_string(days);
+        if let Some(best) = self.days.get(&date) {
+            if best.score >= score {
+                return false;
+            }
+        }
+        self.days.insert(date, DailyResult {
+            score: score,
+            friends: friends,
+            code: result_code(days, score, friends),
+        });
+        true
+    }
+}
+
+fn read_daily_results(ctx: &mut Context) -> GameResult<DailyResults> {
+    let mut file = ctx.filesystem.open(DAILY_FILE)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    serde_json::from_str(&contents)
+        .map_err(|e| GameError::ResourceLoadError(format!("Bad daily results file: {}", e)))
+}
+
+pub fn load_daily_results(ctx: &mut Context) -> DailyResults {
+    if !ctx.filesystem.is_file(DAILY_FILE) {
+        return DailyResults::default();
+    }
+    match read_daily_results(ctx) {
+        Ok(results) => results,
+        Err(e) => {
+            println!("Could not read daily results, starting afresh");
+            println!("Error: {}", e);
+            DailyResults::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_from_days() {
+        assert_eq!(date_string(0), "1970-01-01");
+        assert_eq!(date_string(59), "1970-03-01");
+        assert_eq!(date_string(11016), "2000-02-29");
+        assert_eq!(date_string(20745), "2026-10-19");
+    }
+
+    #[test]
+    fn result_code_reads_back_the_run() {
+        let code = result_code(20745, 42, 12);
+        assert!(code.starts_with("LHF-20261019-S42-F12-"), code);
+        assert_eq!(code.len(), "LHF-20261019-S42-F12-".len() + 4);
+        assert_eq!(code, result_code(20745, 42, 12));
+    }
+
+    #[test]
+    fn result_code_check_catches_edits() {
+        let code = result_code(20745, 42, 12);
+        let check = &code[code.len() - 4..];
+        assert!(!result_code(20745, 43, 12).ends_with(check));
+        assert!(!result_code(20745, 42, 13).ends_with(check));
+        assert!(!result_code(20746, 42, 12).ends_with(check));
+    }
+
+    #[test]
+    fn only_the_best_of_the_day_is_kept() {
+        let mut results = DailyResults::default();
+        assert!(results.record(20745, 10, 4));
+        assert!(!results.record(20745, 8, 6));
+        assert!(!results.record(20745, 10, 6));
+        assert!(results.record(20745, 15, 5));
+        assert!(results.record(20746, 1, 1));
+        let best = results.best(20745).unwrap();
+        assert_eq!((best.score, best.friends), (15, 5));
+        assert_eq!(best.code, result_code(20745, 15, 5));
+        assert!(results.best(20744).is_none());
+    }
+
+    #[test]
+    fn rolls_repeat_per_stream_and_count() {
+        use rand::Rng;
+        let seed = daily_seed(20745);
+        let roll = |stream, n| seeded_rng(seed, stream, n).gen::<u32>();
+        assert_eq!(roll(Stream::Spawn, 3), roll(Stream::Spawn, 3));
+        assert!(roll(Stream::Spawn, 3) != roll(Stream::Spawn, 4));
+        assert!(roll(Stream::Spawn, 3) != roll(Stream::Minigame, 3));
+    }
+}
@@ -0,0 +1,365 @@
+use rand::Rng;
+
+/// ********************************************************************
+/// Difficulty Curve
+///
+/// How hard the game is at any score is read off a curve of points, each
+/// saying how things should be once that many friends have been made.
+/// Between two points everything is blended linearly, and past the last
+/// one it stays put. Levels can bring their own curve under `difficulty`,
+/// otherwise they get the default one below.
+/// ********************************************************************
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Difficulty {
+    /// Friends made this run that this point applies from. Not the
+    /// points scored, which bonuses and penalties move about.
+    pub score: u32,
+    /// Minions to keep in the courtyard
+    pub population: u32,
+    /// Range the ring shrink speed is rolled from
+    pub ring_speed_min: f32,
+    pub ring_speed_max: f32,
+    /// Range the goal ring scale is rolled from
+    pub goal_min: f32,
+    pub goal_max: f32,
+    /// How far off the goal ring a high five can be and still land
+    pub hit_window: f32,
+    /// Seconds added to the clock for each friend
+    pub time_bonus: f32,
+    /// Chance of a high five being something other than the plain ring
+    #[serde(default)]
+    pub variety: f32,
+    /// Most ring passes one high five can chain together
+    #[serde(default = "default_ring_passes")]
+    pub ring_passes: u32,
+}
+
+fn default_ring_passes() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyCurve {
+    /// Sorted by score
+    pub points: Vec<Difficulty>,
+}
+
+impl Default for DifficultyCurve {
+    /// Starts where the game always used to be and ramps up from there
+    fn default() -> Self {
+        DifficultyCurve {
+            points: vec![
+                Difficulty { score: 0, population: 3, ring_speed_min: 1.2, ring_speed_max: 2.3,
+                             goal_min: 0.2, goal_max: 0.8, hit_window: 0.1, time_bonus: 5.0, variety: 0.0,
+                             ring_passes: 1 },
+                Difficulty { score: 10, population: 5, ring_speed_min: 1.5, ring_speed_max: 2.6,
+                             goal_min: 0.2, goal_max: 0.7, hit_window: 0.08, time_bonus: 3.0, variety: 0.2,
+                             ring_passes: 2 },
+                Difficulty { score: 25, population: 8, ring_speed_min: 1.9, ring_speed_max: 3.0,
+                             goal_min: 0.15, goal_max: 0.6, hit_window: 0.06, time_bonus: 2.0, variety: 0.4,
+                             ring_passes: 3 },
+                Difficulty { score: 50, population: 12, ring_speed_min: 2.3, ring_speed_max: 3.5,
+                             goal_min: 0.1, goal_max: 0.5, hit_window: 0.045, time_bonus: 1.0, variety: 0.5,
+                             ring_passes: 4 },
+            ],
+        }
+    }
+}
+
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Rolls from one of the difficulty's ranges. A range that's closed
+/// down to one value, which curves are free to do, just gives that.
+pub fn roll<R: Rng>(rng: &mut R, min: f32, max: f32) -> f32 {
+    if min < max {
+        rng.gen_range(min, max)
+    }
+    else {
+        min
+    }
+}
+
+impl DifficultyCurve {
+    /// Why the curve can't be used, if it can't: the points have to be
+    /// in score order and every range the right way round
+    pub fn check(&self) -> Result<(), String> {
+        for pair in self.points.windows(2) {
+            if pair[1].score < pair[0].score {
+                return Err(format!("difficulty point for score {} comes after score {}",
+                                   pair[1].score, pair[0].score));
+            }
+        }
+        for point in self.points.iter() {
+            if !(point.ring_speed_min <= point.ring_speed_max) {
+                return Err(format!("ring speed range at score {} is backwards", point.score));
+            }
+            if !(point.goal_min <= point.goal_max) {
+                return Err(format!("goal range at score {} is backwards", point.score));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn at(&self, score: u32) -> Difficulty {
+        if self.points.len() == 0 {
+            return DifficultyCurve::default().at(score);
+        }
+        let first = self.points[0];
+        if score <= first.score {
+            return Difficulty { score: score, ..first };
+        }
+        for pair in self.points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            // Saturating, as `check` only keeps unsorted points out of
+            // level files
+            if score < b.score {
+                let span = b.score.saturating_sub(a.score).max(1);
+                let t = score.saturating_sub(a.score) as f32 / span as f32;
+                return Difficulty {
+                    score: score,
+                    population: lerp(a.population as f32, b.population as f32, t) as u32,
+                    ring_speed_min: lerp(a.ring_speed_min, b.ring_speed_min, t),
+                    ring_speed_max: lerp(a.ring_speed_max, b.ring_speed_max, t),
+                    goal_min: lerp(a.goal_min, b.goal_min, t),
+                    goal_max: lerp(a.goal_max, b.goal_max, t),
+                    hit_window: lerp(a.hit_window, b.hit_window, t),
+                    time_bonus: lerp(a.time_bonus, b.time_bonus, t),
+                    variety: lerp(a.variety, b.variety, t),
+                    ring_passes: lerp(a.ring_passes as f32, b.ring_passes as f32, t) as u32,
+                };
+            }
+        }
+        Difficulty { score: score, ..self.points[self.points.len() - 1] }
+    }
+}
+
+/// ********************************************************************
+/// Adaptive Difficulty
+///
+/// On top of the curve, the last few ring attempts are used to guess how
+/// good the player is. Players landing more than `TARGET_SUCCESS` of their
+/// high fives get a tighter window and faster rings, players landing
+/// fewer get a wider window and slower rings.
+/// ********************************************************************
+
+/// Attempts the skill estimate is taken over
+const ATTEMPT_HISTORY: usize = 10;
+/// Attempts needed before anything is adjusted
+const MIN_ATTEMPTS: usize = 3;
+/// Share of high fives we want players to land
+const TARGET_SUCCESS: f32 = 0.7;
+/// How far the window and ring speeds can be pushed either way, at the
+/// very best or very worst skill
+const WINDOW_ADAPT: f32 = 0.4;
+const SPEED_ADAPT: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Attempt {
+    /// How far the ring was off the goal
+    error: f32,
+    /// Ring shrink speed times seconds waited, so roughly how many
+    /// shrinks the player sat through before pressing
+    cycles: f32,
+    hit: bool,
+    /// Window the attempt was judged against
+    window: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkillTracker {
+    attempts: Vec<Attempt>,
+}
+
+impl SkillTracker {
+    pub fn record(&mut self, error: f32, reaction_time: f32, ring_speed: f32, window: f32) {
+        if self.attempts.len() == ATTEMPT_HISTORY {
+            self.attempts.remove(0);
+        }
+        self.attempts.push(Attempt {
+            error: error.abs(),
+            cycles: reaction_time * ring_speed,
+            hit: error.abs() <= window,
+            window: window,
+        });
+    }
+
+    pub fn success_rate(&self) -> f32 {
+        if self.attempts.len() == 0 {
+            return TARGET_SUCCESS;
+        }
+        let hits = self.attempts.iter().filter(|a| a.hit).count();
+        hits as f32 / self.attempts.len() as f32
+    }
+
+    /// From -1 for someone who can't land anything to 1 for someone
+    /// who never misses, quickly and dead on. 0 is right on target.
+    pub fn skill(&self) -> f32 {
+        if self.attempts.len() < MIN_ATTEMPTS {
+            return 0.0;
+        }
+        let count = self.attempts.len() as f32;
+        let rate = self.success_rate();
+        let success = if rate >= TARGET_SUCCESS {
+            (rate - TARGET_SUCCESS) / (1.0 - TARGET_SUCCESS)
+        }
+        else {
+            (rate - TARGET_SUCCESS) / TARGET_SUCCESS
+        };
+        // Landing in the middle of the window rather than the edge. A
+        // window closed to nothing can only be hit dead on.
+        let precision = self.attempts.iter()
+            .map(|a| {
+                let share = if a.window > 0.0 { a.error / a.window } else if a.error > 0.0 { 1.0 } else { 0.0 };
+                (1.0 - 2.0 * share).max(-1.0).min(1.0)
+            })
+            .sum::<f32>() / count;
+        // Pressing on the first shrink rather than waiting for another go
+        let reaction = self.attempts.iter()
+            .map(|a| (1.0 - a.cycles).max(-1.0).min(1.0))
+            .sum::<f32>() / count;
+        (0.6 * success + 0.25 * precision + 0.15 * reaction).max(-1.0).min(1.0)
+    }
+
+    /// The curve's difficulty, nudged towards the player's skill
+    pub fn adjust(&self, base: Difficulty) -> Difficulty {
+        let skill = self.skill();
+        Difficulty {
+            ring_speed_min: base.ring_speed_min * (1.0 + skill * SPEED_ADAPT),
+            ring_speed_max: base.ring_speed_max * (1.0 + skill * SPEED_ADAPT),
+            hit_window: base.hit_window * (1.0 - skill * WINDOW_ADAPT),
+            ..base
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use suspend::SimRng;
+
+    fn point(score: u32, population: u32, goal_min: f32, goal_max: f32) -> Difficulty {
+        Difficulty { score: score, population: population, ring_speed_min: 1.0, ring_speed_max: 2.0,
+                     goal_min: goal_min, goal_max: goal_max, hit_window: 0.1, time_bonus: 2.0,
+                     variety: 0.0, ring_passes: 1 }
+    }
+
+    fn curve() -> DifficultyCurve {
+        DifficultyCurve { points: vec![point(10, 4, 0.2, 0.8), point(20, 8, 0.4, 0.6)] }
+    }
+
+    #[test]
+    fn blends_between_points() {
+        let at = curve().at(15);
+        assert_eq!(at.score, 15);
+        assert_eq!(at.population, 6);
+        assert!((at.goal_min - 0.3).abs() < 1e-6);
+        assert!((at.goal_max - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn holds_the_ends() {
+        let curve = curve();
+        assert_eq!(curve.at(0), Difficulty { score: 0, ..curve.points[0] });
+        assert_eq!(curve.at(10), Difficulty { score: 10, ..curve.points[0] });
+        assert_eq!(curve.at(20), Difficulty { score: 20, ..curve.points[1] });
+        assert_eq!(curve.at(500), Difficulty { score: 500, ..curve.points[1] });
+    }
+
+    #[test]
+    fn empty_curve_is_the_default() {
+        let empty = DifficultyCurve { points: vec![] };
+        assert_eq!(empty.at(30), DifficultyCurve::default().at(30));
+    }
+
+    #[test]
+    fn unsorted_points_do_not_underflow() {
+        let unsorted = DifficultyCurve { points: vec![point(20, 8, 0.4, 0.6), point(10, 4, 0.2, 0.8),
+                                                      point(30, 2, 0.1, 0.2)] };
+        assert!(unsorted.check().is_err());
+        for score in 0..40 {
+            unsorted.at(score);
+        }
+    }
+
+    #[test]
+    fn checks_ranges() {
+        assert!(curve().check().is_ok());
+        assert!(DifficultyCurve::default().check().is_ok());
+        let backwards = DifficultyCurve { points: vec![point(0, 3, 0.8, 0.2)] };
+        assert!(backwards.check().is_err());
+        let closed = DifficultyCurve { points: vec![point(0, 3, 0.5, 0.5)] };
+        assert!(closed.check().is_ok());
+    }
+
+    #[test]
+    fn closed_range_rolls_its_one_value() {
+        let mut rng = SimRng::new(1);
+        assert_eq!(roll(&mut rng, 0.5, 0.5), 0.5);
+        for _ in 0..100 {
+            let rolled = roll(&mut rng, 0.2, 0.8);
+            assert!(rolled >= 0.2 && rolled < 0.8);
+        }
+    }
+
+    fn tracker(error: f32, window: f32, attempts: usize) -> SkillTracker {
+        let mut skill = SkillTracker::default();
+        for _ in 0..attempts {
+            skill.record(error, 0.3, 1.5, window);
+        }
+        skill
+    }
+
+    #[test]
+    fn no_skill_until_enough_attempts() {
+        assert_eq!(tracker(0.0, 0.1, 0).skill(), 0.0);
+        assert_eq!(tracker(0.0, 0.1, MIN_ATTEMPTS - 1).skill(), 0.0);
+        assert!(tracker(0.0, 0.1, MIN_ATTEMPTS).skill() > 0.0);
+        let base = DifficultyCurve::default().at(0);
+        assert_eq!(tracker(0.0, 0.1, MIN_ATTEMPTS - 1).adjust(base), base);
+    }
+
+    #[test]
+    fn only_the_latest_attempts_are_kept() {
+        let mut skill = tracker(0.5, 0.1, ATTEMPT_HISTORY);
+        assert_eq!(skill.success_rate(), 0.0);
+        for _ in 0..ATTEMPT_HISTORY {
+            skill.record(0.0, 0.3, 1.5, 0.1);
+        }
+        assert_eq!(skill.attempts.len(), ATTEMPT_HISTORY);
+        assert_eq!(skill.success_rate(), 1.0);
+    }
+
+    #[test]
+    fn landing_everything_makes_it_harder() {
+        let skill = tracker(0.0, 0.1, ATTEMPT_HISTORY);
+        assert!(skill.skill() > 0.0);
+        let base = DifficultyCurve::default().at(0);
+        let adjusted = skill.adjust(base);
+        assert!(adjusted.hit_window < base.hit_window);
+        assert!(adjusted.ring_speed_min > base.ring_speed_min);
+        assert!(adjusted.ring_speed_max > base.ring_speed_max);
+    }
+
+    #[test]
+    fn missing_everything_makes_it_easier() {
+        let skill = tracker(0.5, 0.1, ATTEMPT_HISTORY);
+        assert!(skill.skill() < 0.0);
+        let base = DifficultyCurve::default().at(0);
+        let adjusted = skill.adjust(base);
+        assert!(adjusted.hit_window > base.hit_window);
+        assert!(adjusted.ring_speed_min < base.ring_speed_min);
+        assert!(adjusted.ring_speed_max < base.ring_speed_max);
+    }
+
+    #[test]
+    fn closed_window_does_not_give_nan() {
+        for &error in [0.0, 0.2].iter() {
+            let skill = tracker(error, 0.0, ATTEMPT_HISTORY).skill();
+            assert!(!skill.is_nan());
+            assert!(skill >= -1.0 && skill <= 1.0);
+        }
+    }
+}
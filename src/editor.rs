@@ -0,0 +1,334 @@
+use std::io::Write;
+
+use ggez::{Context, GameResult, GameError};
+use ggez::event::{Keycode, MouseButton};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point};
+use na;
+use na::geometry::Point2;
+use serde_json;
+
+use archetype::ARCHETYPES;
+use minigame::MINIGAME_KINDS;
+use level::{Region, SiteDef, MinionDef, RingParams};
+use {MainState, SiteKind, ActorType, Actor};
+use {create_player, create_disposal_sites, create_inspector, create_inspector_route, place_level_minions};
+use {world_to_screen_coords, screen_to_world_coords, minion_anchor, draw_text};
+
+/// ********************************************************************
+/// Level Editor
+///
+/// F2 while playing freezes everything and hands the mouse over. The
+/// floor is put back the way the level lays it out first, so a save
+/// holds the level and not wherever the run had got to: the lord goes
+/// back to the start, bodies are cleared away and only the level's own
+/// minions are kept.
+///
+/// * left drag moves minions, disposal sites, the player start and the
+///   inspector's waypoints
+/// * right drag paints an exclusion zone, right click on one removes it
+/// * clicking a minion selects it, then `[` `]` change its ring speed,
+///   `-` `=` its goal ring, Backspace puts it back to random rings,
+///   Tab switches its archetype and M its minigame
+/// * F5 saves the level, F2 goes back to the game
+/// ********************************************************************
+
+/// Saved to the user directory, and picked over the level that ships
+/// with the game when it's there
+pub const EDITED_LEVEL_FILE: &'static str = "/level1_edited.json";
+
+const PICK_RANGE: f32 = 50.0;
+const RING_SPEED_STEP: f32 = 0.1;
+const RING_GOAL_STEP: f32 = 0.05;
+/// Less than this and a right drag counts as a click
+const MIN_ZONE_SIZE: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handle {
+    Minion(usize),
+    Site(usize),
+    PlayerStart,
+    Waypoint(usize),
+}
+
+#[derive(Debug)]
+pub struct EditorState {
+    /// State to go back to when leaving the editor
+    pub return_state: u32,
+    pub dragging: Option<Handle>,
+    pub selected: Option<usize>,
+    /// Corner the exclusion zone being painted started from
+    pub painting: Option<Point2<f32>>,
+    pub mouse: Point2<f32>,
+    pub save_requested: bool,
+    pub message: String,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        EditorState {
+            return_state: 0,
+            dragging: None,
+            selected: None,
+            painting: None,
+            mouse: Point2::origin(),
+            save_requested: false,
+            message: String::new(),
+        }
+    }
+}
+
+/// Puts the floor back the way the level lays it out
+fn reset_floor(game: &mut MainState) {
+    let (x, y) = game.level.player_start;
+    game.player = create_player(x, y);
+    game.sites = create_disposal_sites(&game.level);
+    game.dead_minions.clear();
+    game.carrying = false;
+    game.minions.clear();
+    place_level_minions(game);
+    // Never empty, a level without a route gets a guard post
+    game.inspector_route = create_inspector_route(&game.level);
+    game.inspector = create_inspector(game.inspector_route[0].x, game.inspector_route[0].y);
+    game.inspector_waypoint = 1 % game.inspector_route.len();
+}
+
+pub fn enter_editor(game: &mut MainState) {
+    reset_floor(game);
+    game.editor.selected = None;
+    game.editor.return_state = game.state;
+    game.editor.dragging = None;
+    game.editor.painting = None;
+    game.editor.message = "Editing level, F5 to save, F2 to play".to_string();
+    game.input.fire = false;
+    game.state = 10;
+}
+
+pub fn leave_editor(game: &mut MainState) {
+    game.editor.dragging = None;
+    game.editor.painting = None;
+    game.state = game.editor.return_state;
+}
+
+/// What's under the mouse, if anything
+fn pick(game: &MainState, point: &Point2<f32>) -> Option<Handle> {
+    let mut best = None;
+    let mut best_dist = PICK_RANGE;
+    {
+        let mut consider = |handle: Handle, pos: Point2<f32>| {
+            let dist = na::distance(point, &pos);
+            if dist < best_dist {
+                best_dist = dist;
+                best = Some(handle);
+            }
+        };
+        for x in 0..game.minions.len() {
+            consider(Handle::Minion(x), minion_anchor(&game.minions[x]));
+        }
+        for x in 0..game.sites.len() {
+            consider(Handle::Site(x), game.sites[x].actor.pos);
+        }
+        for x in 0..game.inspector_route.len() {
+            consider(Handle::Waypoint(x), game.inspector_route[x]);
+        }
+        consider(Handle::PlayerStart, game.player.pos);
+    }
+    best
+}
+
+pub fn editor_mouse_down(game: &mut MainState, button: MouseButton, x: i32, y: i32) {
+    let point = screen_to_world_coords(game.screen_width, game.screen_height, x, y);
+    game.editor.mouse = point;
+    match button {
+        MouseButton::Left => {
+            game.editor.dragging = pick(game, &point);
+            if let Some(Handle::Minion(x)) = game.editor.dragging {
+                game.editor.selected = Some(x);
+            }
+        }
+        MouseButton::Right => {
+            game.editor.painting = Some(point);
+        }
+        _ => (),
+    }
+}
+
+pub fn editor_mouse_up(game: &mut MainState, button: MouseButton, x: i32, y: i32) {
+    let point = screen_to_world_coords(game.screen_width, game.screen_height, x, y);
+    match button {
+        MouseButton::Left => {
+            game.editor.dragging = None;
+        }
+        MouseButton::Right => {
+            if let Some(start) = game.editor.painting.take() {
+                let zone = Region::new(start.x.min(point.x), start.y.min(point.y),
+                                       (start.x - point.x).abs(), (start.y - point.y).abs());
+                if zone.w < MIN_ZONE_SIZE && zone.h < MIN_ZONE_SIZE {
+                    game.level.exclusion_zones.retain(|z| !z.contains(&point));
+                }
+                else {
+                    game.level.exclusion_zones.push(zone);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+pub fn editor_mouse_motion(game: &mut MainState, x: i32, y: i32) {
+    let point = screen_to_world_coords(game.screen_width, game.screen_height, x, y);
+    let moved = point - game.editor.mouse;
+    game.editor.mouse = point;
+    match game.editor.dragging {
+        Some(Handle::Minion(x)) => {
+            game.minions[x].pos += moved;
+            game.minions[x].target = game.minions[x].pos;
+        }
+        Some(Handle::Site(x)) => game.sites[x].actor.pos += moved,
+        Some(Handle::Waypoint(x)) => game.inspector_route[x] += moved,
+        Some(Handle::PlayerStart) => game.player.pos += moved,
+        None => (),
+    }
+}
+
+pub fn editor_key(game: &mut MainState, keycode: Keycode) {
+    if keycode == Keycode::F5 {
+        game.editor.save_requested = true;
+        return;
+    }
+    let x = match game.editor.selected {
+        Some(x) if x < game.minions.len() => x,
+        _ => return,
+    };
+    let mut ring = game.minions[x].ring.unwrap_or(RingParams { speed: 1.75, goal: 0.5 });
+    match keycode {
+        Keycode::LeftBracket => ring.speed = (ring.speed - RING_SPEED_STEP).max(0.2),
+        Keycode::RightBracket => ring.speed = (ring.speed + RING_SPEED_STEP).min(5.0),
+        Keycode::Minus => ring.goal = (ring.goal - RING_GOAL_STEP).max(0.1),
+        Keycode::Equals => ring.goal = (ring.goal + RING_GOAL_STEP).min(0.9),
+        Keycode::Backspace => {
+            game.minions[x].ring = None;
+            return;
+        }
+        Keycode::M => {
+            // Cycles through every minigame, then back to rolling one
+            game.minions[x].minigame = match game.minions[x].minigame {
+                None => Some(MINIGAME_KINDS[0]),
+                Some(kind) => {
+                    let next = MINIGAME_KINDS.iter().position(|k| *k == kind).unwrap_or(0) + 1;
+                    MINIGAME_KINDS.get(next).cloned()
+                }
+            };
+            return;
+        }
+        Keycode::Tab => {
+            let next = (game.minions[x].archetype as usize + 1) % ARCHETYPES.len();
+            game.minions[x].archetype = ARCHETYPES[next];
+            return;
+        }
+        _ => return,
+    }
+    game.minions[x].ring = Some(ring);
+}
+
+/// Writes the level as it's laid out on screen right now. Everything on
+/// screen came from the level when the editor opened, see `reset_floor`.
+pub fn save_edited_level(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let mut level = game.level.clone();
+    level.player_start = (game.player.pos.x, game.player.pos.y);
+    level.inspector_route = game.inspector_route.iter().map(|p| (p.x, p.y)).collect();
+    level.minions = game.minions.iter().map(|m| MinionDef { x: m.pos.x, y: m.pos.y, ring: m.ring, archetype: m.archetype, minigame: m.minigame }).collect();
+    level.starting_minions = level.starting_minions.max(level.minions.len() as u32);
+    level.sites = game.sites.iter().map(|site| {
+        let (x, y) = (site.actor.pos.x, site.actor.pos.y);
+        match (site.kind, &site.actor.tag) {
+            (SiteKind::FirePit, _) =>
+                SiteDef::FirePit { x: x, y: y, capacity: site.capacity, burn_time: site.burn_time },
+            (SiteKind::Hide, &ActorType::Closet) =>
+                SiteDef::Closet { x: x, y: y, capacity: site.capacity, find_rate: site.find_rate },
+            (SiteKind::Hide, _) =>
+                SiteDef::Bush { x: x, y: y, capacity: site.capacity, find_rate: site.find_rate },
+        }
+    }).collect();
+
+    let json = serde_json::to_string_pretty(&level)
+        .map_err(|e| GameError::UnknownError(format!("Could not write level: {}", e)))?;
+    let mut file = ctx.filesystem.create(EDITED_LEVEL_FILE)?;
+    file.write_all(json.as_bytes())?;
+    game.level = level;
+    game.editor.message = format!("Saved to {}{}",
+                                  ctx.filesystem.get_user_data_dir().display(), EDITED_LEVEL_FILE);
+    Ok(())
+}
+
+fn draw_region(ctx: &mut Context, game: &MainState, region: &Region, mode: DrawMode) -> GameResult<()> {
+    let center = world_to_screen_coords(game.screen_width, game.screen_height, region.center());
+    graphics::rectangle(ctx, mode, graphics::Rect::new(center.x, center.y, region.w, region.h))
+}
+
+fn draw_marker(ctx: &mut Context, game: &MainState, point: Point2<f32>, radius: f32) -> GameResult<()> {
+    let pos = world_to_screen_coords(game.screen_width, game.screen_height, point);
+    graphics::circle(ctx, DrawMode::Line, Point::new(pos.x, pos.y), radius, 16)
+}
+
+/// Overlays spawn regions, exclusion zones, handles and the selected
+/// minion's ring settings on top of the frozen game
+pub fn draw_editor(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    graphics::set_color(ctx, Color::new(0.2, 0.8, 0.2, 1.0))?;
+    for region in game.level.spawn_regions.iter() {
+        draw_region(ctx, game, region, DrawMode::Line)?;
+    }
+    graphics::set_color(ctx, Color::new(0.9, 0.2, 0.2, 0.35))?;
+    for zone in game.level.exclusion_zones.iter() {
+        draw_region(ctx, game, zone, DrawMode::Fill)?;
+    }
+    if let Some(start) = game.editor.painting {
+        let mouse = game.editor.mouse;
+        let zone = Region::new(start.x.min(mouse.x), start.y.min(mouse.y),
+                               (start.x - mouse.x).abs(), (start.y - mouse.y).abs());
+        draw_region(ctx, game, &zone, DrawMode::Fill)?;
+    }
+
+    graphics::set_color(ctx, Color::new(1.0, 0.9, 0.2, 1.0))?;
+    for x in 0..game.inspector_route.len() {
+        draw_marker(ctx, game, game.inspector_route[x], 8.0)?;
+    }
+    graphics::set_color(ctx, Color::new(0.2, 0.6, 1.0, 1.0))?;
+    draw_marker(ctx, game, game.player.pos, 20.0)?;
+    for site in game.sites.iter() {
+        draw_marker(ctx, game, site.actor.pos, 20.0)?;
+    }
+    let selected: Option<&Actor> = match game.editor.selected {
+        Some(x) if x < game.minions.len() => Some(&game.minions[x]),
+        _ => None,
+    };
+    for (x, minion) in game.minions.iter().enumerate() {
+        let radius = if game.editor.selected == Some(x) { 30.0 } else { 15.0 };
+        draw_marker(ctx, game, minion_anchor(minion), radius)?;
+    }
+
+    let ring_str = match selected {
+        Some(minion) => {
+            let minigame = match minion.minigame {
+                Some(kind) => format!("{:?}", kind),
+                None => "Random".to_string(),
+            };
+            match minion.ring {
+                Some(ring) => format!("{} (Tab)   {} (M)   Ring speed {:.2} [ ]   Goal {:.2} - =   Backspace for random",
+                                      minion.archetype.info().name, minigame, ring.speed, ring.goal),
+                None => format!("{} (Tab)   {} (M)   Random ring, [ ] - = to set one",
+                                minion.archetype.info().name, minigame),
+            }
+        }
+        None => "Click a minion to edit its ring".to_string(),
+    };
+    let font = graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+    let mut message = graphics::Text::new(ctx, &game.editor.message, &font)?;
+    let mut ring_text = graphics::Text::new(ctx, &ring_str, &font)?;
+    graphics::set_color(ctx, graphics::BLACK)?;
+    let message_dest = Point::new((message.width() / 2) as f32 + 20.0, game.screen_height as f32 - 50.0);
+    let ring_dest = Point::new((ring_text.width() / 2) as f32 + 20.0, game.screen_height as f32 - 25.0);
+    draw_text(ctx, &mut message, message_dest)?;
+    draw_text(ctx, &mut ring_text, ring_dest)?;
+    graphics::set_color(ctx, graphics::WHITE)
+}
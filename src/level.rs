@@ -0,0 +1,249 @@
+use std::io::Read;
+
+use ggez::{Context, GameResult, GameError};
+use na::geometry::Point2;
+use rand::Rng;
+use serde_json;
+
+use archetype::Archetype;
+use difficulty::DifficultyCurve;
+use minigame::MinigameKind;
+
+/// ********************************************************************
+/// Level Files
+///
+/// Levels are JSON files in the resources directory describing where
+/// everything goes. All positions are in world coordinates, with the
+/// origin at the centre of the screen and Y pointing up.
+/// ********************************************************************
+
+/// An axis aligned box, `x` and `y` being its bottom left corner
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Region {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Region {
+        Region { x: x, y: y, w: w, h: h }
+    }
+
+    pub fn contains(&self, point: &Point2<f32>) -> bool {
+        point.x >= self.x && point.x <= self.x + self.w
+        && point.y >= self.y && point.y <= self.y + self.h
+    }
+
+    /// Pulls a point back inside the region
+    pub fn clamp(&self, point: &mut Point2<f32>) {
+        point.x = point.x.max(self.x).min(self.x + self.w);
+        point.y = point.y.max(self.y).min(self.y + self.h);
+    }
+
+    pub fn random_point<R: Rng>(&self, rng: &mut R) -> Point2<f32> {
+        Point2::new(self.x + rng.gen::<f32>() * self.w,
+                    self.y + rng.gen::<f32>() * self.h)
+    }
+
+    pub fn center(&self) -> Point2<f32> {
+        Point2::new(self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+}
+
+/// Solid tiles laid over the level, from a Tiled map's collision layer.
+/// Anywhere off the grid counts as solid too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollisionGrid {
+    /// Bottom left corner of the grid
+    pub x: f32,
+    pub y: f32,
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    /// Row by row from the top left, like Tiled stores them
+    pub solid: Vec<bool>,
+}
+
+impl CollisionGrid {
+    pub fn blocked(&self, point: &Point2<f32>) -> bool {
+        let col = ((point.x - self.x) / self.tile_width).floor();
+        let row_up = ((point.y - self.y) / self.tile_height).floor();
+        if col < 0.0 || row_up < 0.0 || col >= self.columns as f32 || row_up >= self.rows as f32 {
+            return true;
+        }
+        let row = self.rows - 1 - row_up as u32;
+        self.solid.get((row * self.columns + col as u32) as usize).cloned().unwrap_or(true)
+    }
+}
+
+/// How a minion's high five ring behaves, instead of rolling it at random
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RingParams {
+    /// How fast the ring shrinks, in scale per second
+    pub speed: f32,
+    /// Scale of the goal ring the shrinking ring has to match
+    pub goal: f32,
+}
+
+/// A minion placed by hand rather than spawned at random
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinionDef {
+    pub x: f32,
+    pub y: f32,
+    pub ring: Option<RingParams>,
+    #[serde(default)]
+    pub archetype: Archetype,
+    /// Minigame this minion always plays, rolled by difficulty if unset
+    #[serde(default)]
+    pub minigame: Option<MinigameKind>,
+}
+
+/// A place bodies can be got rid of
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SiteDef {
+    FirePit { x: f32, y: f32, capacity: usize, burn_time: f32 },
+    Bush { x: f32, y: f32, capacity: usize, find_rate: f32 },
+    Closet { x: f32, y: f32, capacity: usize, find_rate: f32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    /// Image drawn behind everything, if any
+    pub background: Option<String>,
+    pub player_start: (f32, f32),
+    /// Minions spawn and wander inside these
+    pub spawn_regions: Vec<Region>,
+    /// No minion spawns inside these, even when inside a spawn region
+    pub exclusion_zones: Vec<Region>,
+    /// Nobody can walk through these
+    pub obstacles: Vec<Region>,
+    pub sites: Vec<SiteDef>,
+    /// Waypoints the inspector walks between, in order, looping back round
+    pub inspector_route: Vec<(f32, f32)>,
+    pub starting_minions: u32,
+    /// Minions placed at the start before any random ones, counting
+    /// towards `starting_minions`
+    #[serde(default)]
+    pub minions: Vec<MinionDef>,
+    /// Seconds on the clock at the start
+    pub timer: f32,
+    /// Making this many friends wins the level outright. With no goal the
+    /// run only ends when the timer runs out.
+    pub friends_goal: Option<u32>,
+    /// Walls from a Tiled map. When there are some they replace the
+    /// screen edges as the limit of where the lord can walk.
+    #[serde(default)]
+    pub collision: Option<CollisionGrid>,
+    /// How the game ramps up as friends are made
+    #[serde(default)]
+    pub difficulty: DifficultyCurve,
+}
+
+impl Level {
+    /// The original hand placed layout, used when no level file is found
+    pub fn default_layout(screen_width: u32, screen_height: u32) -> Level {
+        let half_w = (screen_width / 2) as f32;
+        let half_h = (screen_height / 2) as f32;
+        let spawn = Region::new(-half_w + 90.0, -half_h + 160.0,
+                                2.0 * half_w - 380.0, 2.0 * half_h - 320.0);
+        let mid_x = spawn.x + spawn.w / 2.0;
+        let top = spawn.y + spawn.h + 60.0;
+        let bottom = spawn.y - 60.0;
+        let right = spawn.x + spawn.w + 150.0;
+        Level {
+            name: "Courtyard".to_string(),
+            background: None,
+            player_start: (500.0, 0.0),
+            spawn_regions: vec![spawn],
+            exclusion_zones: vec![],
+            obstacles: vec![],
+            sites: vec![
+                SiteDef::FirePit { x: half_w - 100.0, y: -half_h + 100.0, capacity: 2, burn_time: 4.0 },
+                SiteDef::FirePit { x: half_w - 100.0, y: half_h - 100.0, capacity: 1, burn_time: 7.0 },
+                SiteDef::Bush { x: -half_w + 80.0, y: -half_h + 80.0, capacity: 2, find_rate: 0.03 },
+                SiteDef::Closet { x: -half_w + 80.0, y: half_h - 80.0, capacity: 1, find_rate: 0.01 },
+            ],
+            inspector_route: vec![(spawn.x, top), (mid_x, top), (right, top),
+                                  (right, bottom), (mid_x, bottom), (spawn.x, bottom)],
+            starting_minions: 3,
+            minions: vec![],
+            timer: 45.0,
+            friends_goal: None,
+            collision: None,
+            difficulty: DifficultyCurve::default(),
+        }
+    }
+
+    /// Whether minions are allowed to spawn on a point
+    pub fn can_spawn_at(&self, point: &Point2<f32>) -> bool {
+        self.spawn_regions.iter().any(|r| r.contains(point))
+        && !self.exclusion_zones.iter().any(|r| r.contains(point))
+        && !self.blocked(point)
+    }
+
+    pub fn blocked(&self, point: &Point2<f32>) -> bool {
+        if let Some(ref grid) = self.collision {
+            if grid.blocked(point) {
+                return true;
+            }
+        }
+        self.obstacles.iter().any(|r| r.contains(point))
+    }
+
+    /// The spawn region a point is in, or the first one if it's in none
+    pub fn region_at(&self, point: &Point2<f32>) -> Region {
+        for region in self.spawn_regions.iter() {
+            if region.contains(point) {
+                return *region;
+            }
+        }
+        self.spawn_regions[0]
+    }
+
+    /// A random point inside one of the spawn regions, picked by area so
+    /// big regions get their fair share of minions
+    pub fn random_spawn_point<R: Rng>(&self, rng: &mut R) -> Point2<f32> {
+        let total: f32 = self.spawn_regions.iter().map(|r| r.w * r.h).sum();
+        let mut pick = rng.gen::<f32>() * total;
+        for region in self.spawn_regions.iter() {
+            pick -= region.w * region.h;
+            if pick <= 0.0 {
+                return region.random_point(rng);
+            }
+        }
+        self.spawn_regions[self.spawn_regions.len() - 1].random_point(rng)
+    }
+}
+
+/// A level that can be picked on the title screen
+pub struct LevelInfo {
+    pub name: &'static str,
+    pub path: &'static str,
+    /// Friends needed in total to unlock it, see `profile`
+    pub unlock_friends: u32,
+}
+
+/// The first level can be replaced by one from the editor or Tiled
+pub const LEVELS: [LevelInfo; 3] = [
+    LevelInfo { name: "Courtyard", path: "/level1.json", unlock_friends: 0 },
+    LevelInfo { name: "Great Hall", path: "/level2.json", unlock_friends: 100 },
+    LevelInfo { name: "Throne Room", path: "/level3.json", unlock_friends: 250 },
+];
+
+pub fn load_level(ctx: &mut Context, path: &str) -> GameResult<Level> {
+    let mut file = ctx.filesystem.open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let level: Level = serde_json::from_str(&contents)
+        .map_err(|e| GameError::ResourceLoadError(format!("Bad level file {}: {}", path, e)))?;
+    if level.spawn_regions.len() == 0 {
+        return Err(GameError::ResourceLoadError(format!("Level {} has no spawn regions", path)));
+    }
+    level.difficulty.check()
+        .map_err(|e| GameError::ResourceLoadError(format!("Bad difficulty curve in level {}: {}", path, e)))?;
+    Ok(level)
+}
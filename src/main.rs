@@ -1,6 +1,32 @@
 extern crate ggez;
 extern crate rand;
 extern crate nalgebra as na;
+extern crate sdl2;
+extern crate serde;
+extern crate serde_json;
+#[macro_use]
+extern crate serde_derive;
+extern crate xml;
+
+mod achievements;
+mod archetype;
+mod daily;
+mod difficulty;
+mod editor;
+mod level;
+mod minigame;
+mod mode;
+mod mutators;
+mod pause;
+mod picker;
+mod profile;
+mod rounds;
+mod scoring;
+mod settings;
+mod spawner;
+mod suspend;
+mod tmx;
+mod tutorial;
 
 use ggez::conf;
 use ggez::event::*;
@@ -9,11 +35,33 @@ use ggez::graphics;
 use ggez::graphics::{Color, DrawMode, Point, FilterMode};
 use ggez::timer;
 use std::time::Duration;
+use std::cmp::Ordering;
 use std::f64;
+use std::f32;
 use na::core::*;
 use na::geometry::Point2;
 
-use rand::{ThreadRng, thread_rng, Rng};
+use rand::{thread_rng, Rng};
+
+use level::{Level, SiteDef, RingParams, LEVELS, load_level};
+use editor::{EditorState, EDITED_LEVEL_FILE};
+use difficulty::{Difficulty, SkillTracker};
+use achievements::{AchievementTracker, GameEvent, ACHIEVEMENTS};
+use archetype::{Archetype, ARCHETYPES, roll_archetype};
+use minigame::{Minigame, MinigameKind, Press, RingGame, Judgement, start_minigame};
+use scoring::{Scoring, Penalty, Tier, COMBO_STEP};
+use mode::{GameMode, GAME_MODES, ENDLESS_KILLS, HighScores};
+use mutators::{Mutators, MUTATOR_COUNT, FIRE_MOVE_TIME, GIANT_MINION_SCALE};
+use rounds::{Rounds, UPGRADES, MAX_UPGRADE_LEVEL};
+use profile::{Profile, ProfileIndex, Skin, SKINS, Unlock, load_profile, save_profile, load_profile_index, save_profile_index};
+use pause::PauseMenu;
+use picker::PickerState;
+use settings::{Action, SettingsMenu};
+use daily::{DailyResults, Stream, today, daily_seed, date_string, seeded_rng};
+use tmx::load_map;
+use spawner::find_spawn_point;
+use suspend::{SimRng, Suspended};
+use tutorial::Tutorial;
 
 const PLANET_LIFE: f32 = 1.0;
 const PLANET_BBOX: f32 = 100.0;
@@ -31,6 +79,34 @@ const MAX_PHYSICS_VEL: f32 = 250.0;
 const PLANET_DENSITY: f32 = 8.0 * 1000.0;
 
 const SHRINK_RATE: f32 = 0.40;
+const SCORE_POPUP_LIFE: f32 = 1.5;
+const TOAST_LIFE: f32 = 3.0;
+
+const MINION_WALK_SPEED: f32 = 60.0; // pixels per second
+const MINION_FLEE_SPEED: f32 = 170.0; // pixels per second
+const MINION_NOTICE_RANGE: f32 = 160.0;
+const MINION_FLEE_TIME: f32 = 4.0;
+
+const WITNESS_NOTICE_RANGE: f32 = 90.0; // seen no matter which way the minion faces
+const WITNESS_SIGHT_RANGE: f32 = 320.0;
+const WITNESS_SIGHT_COS: f32 = 0.3; // about 70 degrees either side of facing
+const WITNESS_TIME_PENALTY: f32 = 3.0;
+
+const CARRY_LIMIT: usize = 2;
+const CARRY_SLOWDOWN: f32 = 0.3; // fraction of speed lost per body's worth of weight
+const PICKUP_RANGE: f32 = 70.0;
+const SITE_RANGE: f32 = 60.0;
+
+const HIDE_SEARCH_RANGE: f32 = 150.0;
+const HIDE_SEARCH_FACTOR: f32 = 8.0; // how much likelier with someone poking around
+
+const INSPECTOR_SPEED: f32 = 90.0; // pixels per second
+const INSPECTOR_SIGHT_RANGE: f32 = 280.0;
+const INSPECTOR_SIGHT_ANGLE: f32 = 0.6; // radians either side of facing
+const INSPECTOR_TURN_RATE: f32 = 3.0; // radians per second
+const MINION_IDLE_MIN: f32 = 1.0;
+const MINION_IDLE_MAX: f32 = 3.5;
+const MINION_WALK_MAX: f32 = 4.0;
 
 const G: f32 = 0.05;
 
@@ -46,44 +122,123 @@ fn vec_from_angle(angle: f32) -> Vector2<f32> {
 struct MainState {
     //planet: Actor,
     player: Actor,
-    fire: Actor,
+    sites: Vec<DisposalSite>,
     attention: Actor,
     minions: Vec<Actor>,
     dead_minions: Vec<Actor>,
     body_reminder: Actor,
-    rings: Vec<Actor>,
+    minigame: Box<dyn Minigame>,
     success_five: Actor,
     state: u32,
     screen_width: u32,
     screen_height: u32,
     input: InputState,
     assets: Assets,
-    score: u32,
+    scoring: Scoring,
+    /// Score events on the HUD, and how long they have left there
+    score_popups: Vec<(String, f32)>,
     score_display: graphics::Text,
     timer_display: graphics::Text,
-    rng: ThreadRng,
+    /// Kept with a suspended run, so it carries on where it left off
+    rng: SimRng,
     timer: f32,
+    carrying: bool,
+    witnesses: u32,
+    inspector: Actor,
+    inspector_route: Vec<Point2<f32>>,
+    inspector_waypoint: usize,
+    caught: bool,
+    won: bool,
+    level: Level,
+    editor: EditorState,
+    difficulty: Difficulty,
+    skill: SkillTracker,
+    /// Archetype of the minion being high fived
+    partner: Archetype,
+    /// Minigame and ring set on that minion, if any
+    high_five_kind: Option<MinigameKind>,
+    high_five_ring: Option<RingParams>,
+    friends: u32,
+    /// How the last minigame went
+    judgement: Option<Judgement>,
+    mode: GameMode,
+    /// Mode highlighted on the title screen
+    mode_choice: usize,
+    /// Where this run's score landed on its mode's table, if it did
+    high_score_place: Option<usize>,
+    kills: u32,
+    /// Spawns and rings are rolled from this, see `daily`
+    seed: u32,
+    spawns: u32,
+    minigames: u32,
+    today: u32,
+    /// Whether this run set the day's best
+    daily_best: bool,
+    mutators: Mutators,
+    /// Seconds until the fire pits move, with the wandering fire mutator
+    fire_move_timer: f32,
+    rounds: Rounds,
+    profile: Profile,
+    /// Every profile on this machine, and which one `profile` is
+    profiles: ProfileIndex,
+    profile_id: u32,
+    picker: PickerState,
+    settings_menu: SettingsMenu,
+    pause_menu: PauseMenu,
+    /// Which of `LEVELS` is loaded
+    level_index: usize,
+    /// What the last run unlocked, for the end screen
+    new_unlocks: Vec<Unlock>,
+    achievements: AchievementTracker,
+    /// Achievements just earned, and how long they have left on screen
+    toasts: Vec<(String, f32)>,
+    /// Whether the profile has changed since it was last saved
+    profile_dirty: bool,
+    /// The profile's run from last time, offered on the title screen
+    suspended: Option<Suspended>,
+    /// The window was closed mid-run, and the run is yet to be written out
+    quit_requested: bool,
+    suspend_written: bool,
+    tutorial: Option<Tutorial>,
+    /// The last spawn found no room, so the next one that doesn't isn't news
+    spawns_full: bool,
 }
 
 impl MainState {
     fn new(ctx: &mut Context) -> GameResult<MainState> {
-        let assets = Assets::new(ctx)?;
+        let mut assets = Assets::new(ctx)?;
+        let profiles = load_profile_index(ctx);
+        let profile_id = match profiles.last {
+            Some(id) if profiles.profiles.iter().any(|entry| entry.id == id) => id,
+            _ => profiles.profiles[0].id,
+        };
+        let profile = load_profile(ctx, profile_id);
+        let picker = PickerState {
+            choice: profiles.profiles.iter().position(|entry| entry.id == profile_id).unwrap_or(0),
+            ..PickerState::default()
+        };
+        let level_index = profile.level;
+        assets.set_skin(profile.skin);
+        let level = load_level_entry(ctx, &mut assets, level_index)?;
         //let planet = create_planet();
-        let player = create_player();
+        let player = create_player(level.player_start.0, level.player_start.1);
         let success_five = create_success_five();
+        let inspector_route = create_inspector_route(&level);
+        let difficulty = level.difficulty.at(0);
         let score_display = graphics::Text::new(ctx, "Score", &graphics::Font::default_font().unwrap())?;
         let timer_display = graphics::Text::new(ctx, "Timer", &graphics::Font::default_font().unwrap())?;
         let s = MainState {
             //planet: planet,
             state: 3,
-            score: 0,
+            scoring: Scoring::default(),
+            score_popups: vec![],
             attention: create_attention(),
             body_reminder: create_body_reminder(),
-            rng: thread_rng(),
-            fire: create_fire(((ctx.conf.window_width / 2) as f32)-100.0, (-1.0*(ctx.conf.window_height / 2) as f32)+100.0),
+            rng: SimRng::new(thread_rng().gen()),
+            sites: create_disposal_sites(&level),
             minions: vec![],
             dead_minions: vec![],
-            rings: vec![create_ring(),create_goal_ring()],
+            minigame: Box::new(RingGame::new(SHRINK_RATE, vec![0.3], 0.1, &Mutators::default())),
             player: player,
             success_five: success_five,
             screen_width: ctx.conf.window_width,
@@ -92,17 +247,124 @@ impl MainState {
             assets: assets,
             score_display: score_display,
             timer_display: timer_display,
-            timer: 45.0,
+            timer: level.timer,
+            carrying: false,
+            witnesses: 0,
+            inspector: create_inspector(inspector_route[0].x, inspector_route[0].y),
+            inspector_waypoint: 1 % inspector_route.len(),
+            inspector_route: inspector_route,
+            caught: false,
+            won: false,
+            level: level,
+            editor: EditorState::default(),
+            difficulty: difficulty,
+            skill: SkillTracker::default(),
+            partner: Archetype::Regular,
+            high_five_kind: None,
+            high_five_ring: None,
+            friends: 0,
+            judgement: None,
+            mode: GameMode::Classic,
+            mode_choice: 0,
+            high_score_place: None,
+            kills: 0,
+            seed: 0,
+            spawns: 0,
+            minigames: 0,
+            today: today(),
+            daily_best: false,
+            mutators: Mutators::default(),
+            fire_move_timer: FIRE_MOVE_TIME,
+            rounds: Rounds::default(),
+            profile: profile,
+            profiles: profiles,
+            profile_id: profile_id,
+            picker: picker,
+            settings_menu: SettingsMenu::default(),
+            pause_menu: PauseMenu::default(),
+            level_index: level_index,
+            new_unlocks: vec![],
+            achievements: AchievementTracker::default(),
+            toasts: vec![],
+            profile_dirty: false,
+            suspended: None,
+            quit_requested: false,
+            suspend_written: false,
+            tutorial: None,
+            spawns_full: false,
         };
         Ok(s)
     }
 
+    /// Everything on the courtyard floor, as it looks while walking around
+    fn draw_floor(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let assets = &mut self.assets;
+        let coords = (self.screen_width, self.screen_height);
+        draw_level(assets,ctx,&self.level,coords)?;
+        for site in self.sites.iter() {
+            draw_actor(assets,ctx,&site.actor,coords)?;
+        }
+        draw_vision_cone(ctx,&self.inspector,coords)?;
+
+        // Everything standing on the floor is drawn back to front,
+        // so whatever is lower on screen ends up in front
+        let mut order = vec![(self.player.pos.y, Depth::Player),
+                             (self.inspector.pos.y, Depth::Inspector)];
+        for x in 0..self.minions.len(){
+            order.push((self.minions[x].pos.y, Depth::Minion(x)));
+        }
+        for x in 0..self.dead_minions.len(){
+            if self.dead_minions[x].carried {
+                // Held in front of the lord
+                order.push((self.player.pos.y - 1.0, Depth::DeadMinion(x)));
+            }
+            else {
+                order.push((self.dead_minions[x].pos.y, Depth::DeadMinion(x)));
+            }
+        }
+        order.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+        for &(_, ref depth) in order.iter() {
+            match *depth {
+                Depth::Player => draw_player(assets,ctx,&mut self.player,coords)?,
+                Depth::Inspector => draw_actor(assets,ctx,&self.inspector,coords)?,
+                Depth::Minion(x) => draw_actor(assets,ctx,&self.minions[x],coords)?,
+                Depth::DeadMinion(x) => draw_actor(assets,ctx,&self.dead_minions[x],coords)?,
+            }
+        }
+
+        if self.attention.life == 1.0 {
+            draw_actor(assets,ctx, &self.attention,coords)?;
+        }
+        if self.body_reminder.life == 1.0 {
+            draw_actor(assets,ctx, &self.body_reminder,coords)?;
+        }
+        Ok(())
+    }
+
     fn update_ui(&mut self, ctx: &mut Context){
         let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18).unwrap();
-        let score_str = format!("Score: {}", self.score);
+        let score_str = if self.scoring.combo >= COMBO_STEP {
+            format!("Score: {}   Combo x{}", self.scoring.total, self.scoring.multiplier())
+        }
+        else {
+            format!("Score: {}", self.scoring.total)
+        };
         let score_text = graphics::Text::new(ctx, &score_str, font).unwrap();
 
-        let timer_str = format!("Timer: {}", self.timer as u32);
+        let timer_str = if self.mode.rounds() {
+            format!("Timer: {}   Round {}: {}/{}", self.timer as u32, self.rounds.round,
+                    self.rounds.friends, self.rounds.quota())
+        }
+        else if self.mode.timed() {
+            format!("Timer: {}", self.timer as u32)
+        }
+        else if self.mode == GameMode::Endless {
+            format!("Lives: {}", ENDLESS_KILLS.saturating_sub(self.kills))
+        }
+        else {
+            self.mode.name().to_string()
+        };
         let timer_text = graphics::Text::new(ctx, &timer_str, font).unwrap();
         self.score_display = score_text;
         self.timer_display = timer_text;
@@ -118,7 +380,11 @@ struct InputState {
     xaxis: f32,
     yaxis: f32,
     fire: bool,
+    pickup: bool,
+    drop: bool,
     any_key: bool,
+    /// Keys pressed this frame, for the high five minigames
+    presses: Vec<Press>,
 }
 
 impl Default for InputState {
@@ -127,7 +393,10 @@ impl Default for InputState {
             xaxis: 0.0,
             yaxis: 0.0,
             fire: false,
+            pickup: false,
+            drop: false,
             any_key: false,
+            presses: vec![],
         }
     }
 }
@@ -138,7 +407,6 @@ impl Default for InputState {
 
 struct Assets {
     dead_minion_image: graphics::Image,
-    minion_image: graphics::Image,
     ring_image: graphics::Image,
     success_five_image: graphics::Image,
     attention_image: graphics::Image,
@@ -151,12 +419,21 @@ struct Assets {
     player_image3_left: graphics::Image,
     body_reminder_image: graphics::Image,
     start1_image: graphics::Image,
-    start2_image: graphics::Image,
-    start3_image: graphics::Image,
-    start4_image: graphics::Image,
-    controls_image: graphics::Image,
     loading_image: graphics::Image,
     end_screen_image: graphics::Image,
+    inspector_image: graphics::Image,
+    bush_image: graphics::Image,
+    closet_image: graphics::Image,
+    background_image: Option<graphics::Image>,
+    /// Tileset images of a Tiled map, and every tile to draw from them
+    map_tilesets: Vec<graphics::Image>,
+    map_tiles: Vec<(usize, graphics::DrawParam)>,
+    /// One per archetype, in the order of `ARCHETYPES`, with the tint to
+    /// draw it with
+    archetype_images: Vec<(graphics::Image, Color)>,
+    /// Lord skin the `player_image` frames are drawn as, and its tint
+    skin: Skin,
+    player_tint: Color,
 }
 
 impl Assets {
@@ -164,6 +441,11 @@ impl Assets {
         let ring_image = graphics::Image::new(ctx, "/ring.png")?;
         let dead_minion_image = sprite(ctx,"/dead_minion.png")?;
         let minion_image = sprite(ctx,"/minion.png")?;
+        // Archetypes are the plain minion, tinted
+        let mut archetype_images = vec![];
+        for archetype in ARCHETYPES.iter() {
+            archetype_images.push((minion_image.clone(), archetype.info().tint));
+        }
         let player_image1_right = sprite(ctx, "/boss1_right.png")?;
         let player_image2_right = sprite(ctx, "/boss2_right.png")?;
         let player_image3_right = sprite(ctx, "/boss3_right.png")?;
@@ -171,11 +453,10 @@ impl Assets {
         let player_image2_left = sprite(ctx, "/boss2_left.png")?;
         let player_image3_left = sprite(ctx, "/boss3_left.png")?;
         let start1_image = sprite(ctx, "/start_screen1.png")?;
-        let start2_image = sprite(ctx, "/start_screen2.png")?;
-        let start3_image = sprite(ctx, "/start_screen3.png")?;
-        let start4_image = sprite(ctx, "/start_screen4.png")?;
-        let controls_image = sprite(ctx, "/controls.png")?;
         let loading_image = sprite(ctx, "/loading.png")?;
+        let inspector_image = sprite(ctx, "/enemy.png")?;
+        let bush_image = sprite(ctx, "/bush.png")?;
+        let closet_image = sprite(ctx, "/closet.png")?;
 
         let body_reminder_image = sprite(ctx, "/body_reminder.png")?;
 
@@ -187,7 +468,6 @@ impl Assets {
         Ok(Assets{
             ring_image: ring_image,
             success_five_image: success_five_image,
-            minion_image: minion_image,
             dead_minion_image: dead_minion_image,
             body_reminder_image: body_reminder_image,
             player_image1_right: player_image1_right,
@@ -197,20 +477,31 @@ impl Assets {
             player_image2_left: player_image2_left,
             player_image3_left: player_image3_left,
             start1_image: start1_image,
-            start2_image: start2_image,
-            start3_image: start3_image,
-            start4_image: start4_image,
-            controls_image: controls_image,
             loading_image: loading_image,
             end_screen_image: end_screen_image,
             attention_image: attention_image,
             fire_image: fire_image,
+            inspector_image: inspector_image,
+            bush_image: bush_image,
+            closet_image: closet_image,
+            background_image: None,
+            map_tilesets: vec![],
+            map_tiles: vec![],
+            archetype_images: archetype_images,
+            skin: Skin::Classic,
+            player_tint: graphics::WHITE,
         })
     }
 
+    /// Skins are the classic lord, tinted
+    fn set_skin(&mut self, skin: Skin) {
+        self.player_tint = skin.info().tint;
+        self.skin = skin;
+    }
+
     fn actor_image(&mut self, actor: &Actor) -> &mut graphics::Image {
         match actor.tag {
-            ActorType::Minion => &mut self.minion_image,
+            ActorType::Minion => &mut self.archetype_images[actor.archetype as usize].0,
             ActorType::Attention => &mut self.attention_image,
             ActorType::SuccessFive => &mut self.success_five_image,
             ActorType::Ring => &mut self.ring_image,
@@ -218,8 +509,81 @@ impl Assets {
             ActorType::Fire => &mut self.fire_image,
             ActorType::Player => &mut self.player_image1_right,
             ActorType::BodyReminder => &mut self.body_reminder_image,
+            ActorType::Inspector => &mut self.inspector_image,
+            ActorType::Bush => &mut self.bush_image,
+            ActorType::Closet => &mut self.closet_image,
+        }
+    }
+}
+
+/// Loads a Tiled map, readying its tiles for drawing, and returns the
+/// level it describes
+fn load_tiled_level(ctx: &mut Context, assets: &mut Assets, path: &str) -> GameResult<Level> {
+    let map = load_map(ctx, path)?;
+    let (screen_w, screen_h) = (ctx.conf.window_width, ctx.conf.window_height);
+    assets.map_tilesets.clear();
+    for set in map.tilesets.iter() {
+        assets.map_tilesets.push(sprite(ctx, &set.image)?);
+    }
+    assets.map_tiles.clear();
+    for tile in map.placed_tiles() {
+        let dest = world_to_screen_coords(screen_w, screen_h, Point2::new(tile.x, tile.y));
+        let (u, v, w, h) = tile.src;
+        assets.map_tiles.push((tile.tileset, graphics::DrawParam{
+            src: graphics::Rect::new(u, v, w, h),
+            dest: Point::new(dest.x, dest.y),
+            ..Default::default()
+        }));
+    }
+    Ok(map.to_level())
+}
+
+/// Loads one of the `LEVELS`, falling back on the default layout. A level
+/// saved from the editor wins over a map made in Tiled, which wins over
+/// the first level's plain file.
+fn load_level_entry(ctx: &mut Context, assets: &mut Assets, index: usize) -> GameResult<Level> {
+    assets.background_image = None;
+    assets.map_tilesets.clear();
+    assets.map_tiles.clear();
+    let loaded = if index != 0 {
+        load_level(ctx, LEVELS[index].path)
+    }
+    else if ctx.filesystem.is_file(EDITED_LEVEL_FILE) {
+        load_level(ctx, EDITED_LEVEL_FILE)
+    }
+    else if ctx.filesystem.is_file("/level1.tmx") {
+        load_tiled_level(ctx, assets, "/level1.tmx")
+    }
+    else {
+        load_level(ctx, LEVELS[0].path)
+    };
+    let level = match loaded {
+        Ok(level) => level,
+        Err(e) => {
+            println!("Could not load level, using the default layout");
+            println!("Error: {}", e);
+            Level::default_layout(ctx.conf.window_width, ctx.conf.window_height)
         }
+    };
+    if let Some(ref background) = level.background {
+        assets.background_image = Some(sprite(ctx, background)?);
     }
+    Ok(level)
+}
+
+/// Swaps the loaded level for another of the `LEVELS`, before a run starts
+fn switch_level(game: &mut MainState, ctx: &mut Context, index: usize) -> GameResult<()> {
+    let level = load_level_entry(ctx, &mut game.assets, index)?;
+    game.player = create_player(level.player_start.0, level.player_start.1);
+    game.sites = create_disposal_sites(&level);
+    game.inspector_route = create_inspector_route(&level);
+    game.inspector = create_inspector(game.inspector_route[0].x, game.inspector_route[0].y);
+    // A one point route is a guard post
+    game.inspector_waypoint = 1 % game.inspector_route.len();
+    game.difficulty = level.difficulty.at(0);
+    game.level = level;
+    game.level_index = index;
+    Ok(())
 }
 
 fn sprite(ctx: &mut Context, s: &str) -> GameResult<graphics::Image> {
@@ -231,8 +595,7 @@ fn sprite(ctx: &mut Context, s: &str) -> GameResult<graphics::Image> {
 /// Actor Code
 /// ********************************************************************
 
-#[derive(Debug)]
-#[derive(PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 enum ActorType {
     Fire,
     SuccessFive,
@@ -242,19 +605,109 @@ enum ActorType {
     Player,
     Attention,
     BodyReminder,
+    Inspector,
+    Bush,
+    Closet,
 }
 
-#[derive(Debug)]
+/// What a minion is currently up to. Only minions make use of this,
+/// every other actor just sits in `Idle`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Behaviour {
+    Idle,
+    Walk,
+    Greet,
+    Flee,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Actor {
     tag: ActorType,
+    #[serde(with = "suspend::point2")]
     pos: Point2<f32>,
     facing: f32,
+    #[serde(with = "suspend::vector2")]
     velocity: Vector2<f32>,
+    #[serde(with = "suspend::vector2")]
     accel: Vector2<f32>,
     rvel: f32,
     bbox_size: f32,
+    #[serde(with = "suspend::point")]
     scale: Point,
     life: f32,
+    behaviour: Behaviour,
+    #[serde(with = "suspend::point2")]
+    target: Point2<f32>,
+    behaviour_time: f32,
+    carried: bool,
+    ring: Option<RingParams>,
+    archetype: Archetype,
+    /// Minigame this minion always plays, instead of rolling one
+    minigame: Option<MinigameKind>,
+    /// Minion has told on the bodies lying around, and won't again until
+    /// the floor has been cleared
+    #[serde(default)]
+    reported: bool,
+}
+
+/// *****************************************************
+/// Disposal Sites
+/// *****************************************************
+
+/// Fire pits get rid of bodies for good once they've burned. Bushes and
+/// closets only stash them, and there's always a chance someone finds them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum SiteKind {
+    FirePit,
+    Hide,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DisposalSite {
+    kind: SiteKind,
+    actor: Actor,
+    capacity: usize,
+    burn_time: f32,
+    find_rate: f32,
+    /// Bodies in the site, along with how long until they're burned up
+    bodies: Vec<(Actor, f32)>,
+}
+
+fn create_fire_pit(posx: f32, posy: f32, capacity: usize, burn_time: f32) -> DisposalSite {
+    DisposalSite {
+        kind: SiteKind::FirePit,
+        actor: create_fire(posx, posy),
+        capacity: capacity,
+        burn_time: burn_time,
+        find_rate: 0.0,
+        bodies: vec![],
+    }
+}
+
+fn create_hiding_spot(actor: Actor, capacity: usize, find_rate: f32) -> DisposalSite {
+    DisposalSite {
+        kind: SiteKind::Hide,
+        actor: actor,
+        capacity: capacity,
+        burn_time: 0.0,
+        find_rate: find_rate,
+        bodies: vec![],
+    }
+}
+
+fn create_disposal_sites(level: &Level) -> Vec<DisposalSite> {
+    let mut sites = vec![];
+    for def in level.sites.iter() {
+        sites.push(match *def {
+            SiteDef::FirePit { x, y, capacity, burn_time } =>
+                create_fire_pit(x, y, capacity, burn_time),
+            SiteDef::Bush { x, y, capacity, find_rate } =>
+                create_hiding_spot(create_bush(x, y), capacity, find_rate),
+            SiteDef::Closet { x, y, capacity, find_rate } =>
+                create_hiding_spot(create_closet(x, y), capacity, find_rate),
+        });
+    }
+    sites
 }
 
 /// *****************************************************
@@ -271,6 +724,14 @@ fn create_body_reminder() -> Actor {
         bbox_size: PLANET_BBOX,
         life: 1.0,
         scale: Point::new(1.0,1.0),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 
@@ -285,6 +746,58 @@ fn create_fire(posx: f32, posy: f32) -> Actor {
         bbox_size: PLANET_BBOX,
         life: PLANET_LIFE,
         scale: Point::new(1.0,1.0),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
+    }
+}
+
+fn create_bush(posx: f32, posy: f32) -> Actor {
+    Actor{
+        tag: ActorType::Bush,
+        pos: Point2::new(posx,posy),
+        facing: 0.0,
+        velocity: Vector2::zeros(),
+        accel: Vector2::zeros(),
+        rvel: 0.0,
+        bbox_size: PLANET_BBOX,
+        life: PLANET_LIFE,
+        scale: Point::new(0.6,0.6),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
+    }
+}
+
+fn create_closet(posx: f32, posy: f32) -> Actor {
+    Actor{
+        tag: ActorType::Closet,
+        pos: Point2::new(posx,posy),
+        facing: 0.0,
+        velocity: Vector2::zeros(),
+        accel: Vector2::zeros(),
+        rvel: 0.0,
+        bbox_size: PLANET_BBOX,
+        life: PLANET_LIFE,
+        scale: Point::new(0.6,0.6),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 
@@ -299,6 +812,14 @@ fn create_attention() -> Actor {
         bbox_size: PLANET_BBOX,
         life: 1.0,
         scale: Point::new(1.0,1.0),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 fn create_success_five() -> Actor {
@@ -312,6 +833,14 @@ fn create_success_five() -> Actor {
         bbox_size: PLANET_BBOX,
         life: SUCCESS_LIFE,
         scale: Point::new(1.0,1.0),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 fn create_ring() -> Actor {
@@ -325,10 +854,20 @@ fn create_ring() -> Actor {
         bbox_size: PLANET_BBOX,
         life: PLANET_LIFE,
         scale: Point::new(1.0,1.0),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 
-fn create_goal_ring() -> Actor {
+/// With the invisible goal mutator the goal ring has no life, and the
+/// minigames don't draw it
+fn create_goal_ring(mutators: &Mutators) -> Actor {
     Actor{
         tag: ActorType::Ring,
         pos: Point2::origin(),
@@ -337,8 +876,16 @@ fn create_goal_ring() -> Actor {
         accel: Vector2::zeros(),
         rvel: 0.0,
         bbox_size: PLANET_BBOX,
-        life: PLANET_LIFE,
+        life: if mutators.invisible_goal { 0.0 } else { PLANET_LIFE },
         scale: Point::new(0.3,0.3),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 
@@ -353,10 +900,19 @@ fn create_dead_minion(posx: f32, posy: f32) -> Actor {
         bbox_size: PLANET_BBOX,
         life: PLANET_LIFE,
         scale: Point::new(0.5,0.5),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 
-fn create_minion(posx: f32, posy: f32) -> Actor {
+fn create_minion(posx: f32, posy: f32, mutators: &Mutators) -> Actor {
+    let size = if mutators.giant_minions { 0.5 * GIANT_MINION_SCALE } else { 0.5 };
     Actor{
         tag: ActorType::Minion,
         pos: Point2::new(posx,posy),
@@ -366,14 +922,58 @@ fn create_minion(posx: f32, posy: f32) -> Actor {
         rvel: 0.0,
         bbox_size: PLANET_BBOX,
         life: PLANET_LIFE,
-        scale: Point::new(0.5,0.5),
+        scale: Point::new(size,size),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
+    }
+}
+
+/// The inspector's `facing` is an angle in radians, see `vec_from_angle`
+fn create_inspector(posx: f32, posy: f32) -> Actor {
+    Actor{
+        tag: ActorType::Inspector,
+        pos: Point2::new(posx,posy),
+        facing: 0.0,
+        velocity: Vector2::zeros(),
+        accel: Vector2::zeros(),
+        rvel: 0.0,
+        bbox_size: PLAYER_BBOX,
+        life: 1.0,
+        scale: Point::new(0.6,0.6),
+        behaviour: Behaviour::Walk,
+        target: Point2::new(posx,posy),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
+    }
+}
+
+/// The level's patrol route. A level without one gets an inspector who
+/// stands guard in the middle of the first spawn region.
+fn create_inspector_route(level: &Level) -> Vec<Point2<f32>> {
+    let mut route = vec![];
+    for &(x, y) in level.inspector_route.iter() {
+        route.push(Point2::new(x, y));
+    }
+    if route.len() == 0 {
+        route.push(level.spawn_regions[0].center());
     }
+    route
 }
 
-fn create_player() -> Actor {
+fn create_player(posx: f32, posy: f32) -> Actor {
     Actor{
         tag: ActorType::Player,
-        pos: Point2::new(500.0,0.0),
+        pos: Point2::new(posx,posy),
         facing: -1.0,
         velocity: Vector2::new(0.0,0.0),
         accel: Vector2::zeros(),
@@ -381,6 +981,14 @@ fn create_player() -> Actor {
         bbox_size: PLAYER_BBOX,
         life: PLAYER_LIFE,
         scale: Point::new(1.0,1.0),
+        behaviour: Behaviour::Idle,
+        target: Point2::origin(),
+        behaviour_time: 0.0,
+        carried: false,
+        ring: None,
+        archetype: Archetype::Regular,
+        minigame: None,
+        reported: false,
     }
 }
 
@@ -388,17 +996,18 @@ fn create_player() -> Actor {
 /// Actor Helping Functions
 /// **********************************************************************
 
-fn player_handle_input(actor: &mut Actor, input: &InputState, dt: f32) {
+fn player_handle_input(actor: &mut Actor, input: &InputState, dt: f32, speed: f32, mutators: &Mutators) {
     //actor.facing += dt * PLAYER_TURN_RATE * input.xaxis;
-    match input.xaxis as i32{
+    let xaxis = if mutators.mirrored { -input.xaxis } else { input.xaxis };
+    match xaxis as i32{
         0 => {
             actor.velocity.x = 0.0;
         }
         1 => {
-            actor.velocity.x += PLAYER_THRUST*(dt);
+            actor.velocity.x += PLAYER_THRUST*speed*(dt);
         }
         -1 => {
-            actor.velocity.x -= PLAYER_THRUST*(dt);
+            actor.velocity.x -= PLAYER_THRUST*speed*(dt);
         }
         _ => (), // Do nothing
     }
@@ -407,51 +1016,474 @@ fn player_handle_input(actor: &mut Actor, input: &InputState, dt: f32) {
             actor.velocity.y = 0.0;
         }
         1 => {
-            actor.velocity.y += PLAYER_THRUST*(dt);
+            actor.velocity.y += PLAYER_THRUST*speed*(dt);
         }
         -1 => {
-            actor.velocity.y -= PLAYER_THRUST*(dt);
+            actor.velocity.y -= PLAYER_THRUST*speed*(dt);
         }
         _ => (), // Do nothing
 
     }
 }
 
+/// The spot in front of a minion where the player has to stand to high five it
+fn minion_anchor(minion: &Actor) -> Point2<f32> {
+    minion.pos + Vector2::new(75.0,0.0)
+}
+
+/// Whether a minion can see a point: anything right next to it, or
+/// anything within its line of sight in the direction it is facing
+fn minion_can_see(minion: &Actor, point: &Point2<f32>) -> bool {
+    let to_point = *point - minion_anchor(minion);
+    let dist = to_point.norm();
+    if dist < WITNESS_NOTICE_RANGE {
+        return true;
+    }
+    if dist > WITNESS_SIGHT_RANGE {
+        return false;
+    }
+    to_point.x * minion.facing / dist > WITNESS_SIGHT_COS
+}
+
+/// Spawns a minion somewhere free, returning false if there was no room
 fn add_minion(game: &mut MainState) -> bool {
-    let x_coord = game.rng.gen_range(-1.0*(game.screen_width/2)as f32 + 90.0, (game.screen_width/2) as f32 - 290.0);
-    let y_coord = game.rng.gen_range(-1.0*(game.screen_height/2)as f32 + 160.0, (game.screen_height/2) as f32 - 160.0);
-    let new_minion = create_minion(x_coord,y_coord);
+    let taken: Vec<Point2<f32>> = game.minions.iter().map(|m| m.pos).collect();
+    let mut rng = seeded_rng(game.seed, Stream::Spawn, game.spawns);
+    // The tutorial's minions don't use up the run's spawns
+    if game.tutorial.is_none() {
+        game.spawns += 1;
+    }
+    match find_spawn_point(&game.level, &taken, &mut rng) {
+        Some(spot) => {
+            let mut minion = create_minion(spot.x,spot.y,&game.mutators);
+            // Everyone's daily challenge has the same minions in it
+            let friends = if game.mode.daily() { 0 } else { game.profile.total_friends };
+            minion.archetype = roll_archetype(&mut rng, friends);
+            game.minions.push(minion);
+            game.spawns_full = false;
+            true
+        }
+        None => {
+            if !game.spawns_full {
+                println!("No room left to spawn a minion, {} already out", game.minions.len());
+                game.spawns_full = true;
+            }
+            false
+        }
+    }
+}
+
+/// Puts out the level's hand placed minions
+fn place_level_minions(game: &mut MainState) {
+    for def in game.level.minions.iter() {
+        let mut minion = create_minion(def.x, def.y, &game.mutators);
+        minion.ring = def.ring;
+        minion.archetype = def.archetype;
+        minion.minigame = def.minigame;
+        game.minions.push(minion);
+    }
+}
 
-    let mut not_too_close = true;
+/// Puts out the level's hand placed minions, then random ones up to
+/// the level's starting count
+fn spawn_starting_minions(game: &mut MainState) {
+    place_level_minions(game);
+    let placed = game.level.minions.len() as u32;
+    for _x in placed..game.level.starting_minions{
+        if !add_minion(game) {
+            break;
+        }
+    }
+}
 
+/// Spawns minions until there are as many as the difficulty calls for,
+/// always replacing at least the one just befriended or killed
+fn populate(game: &mut MainState) {
+    // The tutorial brings its own minion
+    if game.tutorial.is_some() {
+        return;
+    }
+    let target = (game.difficulty.population as usize).max(game.minions.len() + 1);
+    while game.minions.len() < target {
+        if !add_minion(game) {
+            break;
+        }
+    }
+}
+
+/// Runs the minion state machine: minions idle for a while, then wander
+/// to a random spot inside their spawn region. They stop and turn to the
+/// player when the lord walks up to them, and run from any dead minion
+/// they can see.
+fn update_minions(game: &mut MainState, dt: f32) {
     for x in 0..game.minions.len() {
-        if ((game.minions[x].pos.x - new_minion.pos.x).abs() < 170.0 && (game.minions[x].pos.y - new_minion.pos.y).abs() < 310.0) {
-            //println!("{}",(game.minions[x].pos.x - new_minion.pos.x).abs());
-            not_too_close = false;
+        let anchor = minion_anchor(&game.minions[x]);
+        let region = game.level.region_at(&game.minions[x].pos);
+
+        // A body lying around, or the lord dragging one, scares the minion off
+        let mut scare: Option<Point2<f32>> = None;
+        for y in 0..game.dead_minions.len() {
+            if minion_can_see(&game.minions[x], &game.dead_minions[y].pos) {
+                scare = Some(game.dead_minions[y].pos);
+                break;
+            }
+        }
+        if scare.is_none() && game.carrying && minion_can_see(&game.minions[x], &game.player.pos) {
+            scare = Some(game.player.pos);
+        }
+
+        let floor_clear = game.dead_minions.len() == 0 && !game.carrying;
+        let minion = &mut game.minions[x];
+        minion.behaviour_time -= dt;
+        if floor_clear {
+            minion.reported = false;
+        }
+
+        if let Some(body) = scare {
+            // Only the first sighting costs anything. A minion that runs
+            // off and comes back to the same mess isn't going to tell
+            // anyone twice.
+            if !minion.reported {
+                game.timer -= WITNESS_TIME_PENALTY;
+                game.witnesses += 1;
+                game.scoring.penalty(Penalty::Witness);
+                minion.reported = true;
+            }
+            minion.behaviour = Behaviour::Flee;
+            minion.target = body;
+            minion.behaviour_time = MINION_FLEE_TIME;
+        }
+
+        if minion.behaviour == Behaviour::Flee && minion.behaviour_time > 0.0 {
+            let mut away = anchor - minion.target;
+            if away.norm_squared() == 0.0 {
+                away = Vector2::new(1.0,0.0);
+            }
+            minion.velocity = away.normalize() * MINION_FLEE_SPEED;
+        }
+        else if na::distance(&game.player.pos, &anchor) < MINION_NOTICE_RANGE {
+            minion.velocity = Vector2::zeros();
+            minion.behaviour = Behaviour::Greet;
+            if game.player.pos.x > anchor.x {
+                minion.facing = 1.0;
+            }
+            else {
+                minion.facing = -1.0;
+            }
+        }
+        else {
+            match minion.behaviour {
+                Behaviour::Walk => {
+                    if na::distance(&minion.pos, &minion.target) < 5.0 || minion.behaviour_time <= 0.0 {
+                        minion.velocity = Vector2::zeros();
+                        minion.behaviour = Behaviour::Idle;
+                        minion.behaviour_time = game.rng.gen_range(MINION_IDLE_MIN, MINION_IDLE_MAX);
+                    }
+                }
+                Behaviour::Idle => {
+                    if minion.behaviour_time <= 0.0 {
+                        minion.target = region.random_point(&mut game.rng);
+                        let to_target = minion.target - minion.pos;
+                        minion.velocity = if to_target.norm_squared() > 0.0 {
+                            to_target.normalize() * MINION_WALK_SPEED
+                        }
+                        else {
+                            Vector2::zeros()
+                        };
+                        minion.behaviour = Behaviour::Walk;
+                        minion.behaviour_time = MINION_WALK_MAX;
+                    }
+                }
+                // Calmed down or the lord walked off, have a breather first
+                Behaviour::Greet | Behaviour::Flee => {
+                    minion.velocity = Vector2::zeros();
+                    minion.behaviour = Behaviour::Idle;
+                    minion.behaviour_time = game.rng.gen_range(MINION_IDLE_MIN, MINION_IDLE_MAX);
+                }
+            }
+        }
+
+        minion.pos += minion.velocity*dt;
+
+        // Walked into something, give up on wherever it was going
+        let mut stopped = false;
+        if game.level.blocked(&minion.pos) {
+            minion.pos -= minion.velocity*dt;
+            stopped = true;
+        }
+        if !region.contains(&minion.pos) {
+            region.clamp(&mut minion.pos);
+            stopped = true;
+        }
+        if stopped && minion.behaviour == Behaviour::Walk {
+            minion.velocity = Vector2::zeros();
+            minion.behaviour_time = 0.0;
+        }
+        if minion.velocity.x > 0.0 {
+            minion.facing = 1.0;
+        }
+        else if minion.velocity.x < 0.0 {
+            minion.facing = -1.0;
+        }
+    }
+}
+
+/// Whether a point falls inside the inspector's vision cone
+fn inspector_can_see(inspector: &Actor, point: &Point2<f32>) -> bool {
+    let to_point = *point - inspector.pos;
+    let dist = to_point.norm();
+    if dist > INSPECTOR_SIGHT_RANGE {
+        return false;
+    }
+    if dist == 0.0 {
+        return true;
+    }
+    to_point.dot(&vec_from_angle(inspector.facing)) / dist > INSPECTOR_SIGHT_ANGLE.cos()
+}
+
+/// Walks the inspector along its patrol route, turning to face where it is
+/// going, and checks the vision cone for evidence. A body that hasn't been
+/// burned yet or the lord dragging one ends the run.
+fn update_inspector(game: &mut MainState, dt: f32) {
+    {
+        let inspector = &mut game.inspector;
+        if na::distance(&inspector.pos, &game.inspector_route[game.inspector_waypoint]) < 5.0 {
+            game.inspector_waypoint = (game.inspector_waypoint + 1) % game.inspector_route.len();
+        }
+        let to_waypoint = game.inspector_route[game.inspector_waypoint] - inspector.pos;
+        // A route with one waypoint just has the inspector stand on it
+        let standing = game.inspector_route.len() == 1 && to_waypoint.norm_squared() < 25.0;
+        if to_waypoint.norm_squared() > 0.0 && !standing {
+            inspector.velocity = to_waypoint.normalize() * INSPECTOR_SPEED;
+        }
+        else {
+            inspector.velocity = Vector2::zeros();
+        }
+        inspector.pos += inspector.velocity*dt;
+
+        // Turn the head gradually so the cone sweeps round the corners,
+        // and keep looking the same way while standing still
+        let heading = if inspector.velocity.norm_squared() > 0.0 {
+            inspector.velocity.x.atan2(inspector.velocity.y)
+        }
+        else {
+            inspector.facing
+        };
+        let mut turn = heading - inspector.facing;
+        while turn > f32::consts::PI {
+            turn -= 2.0 * f32::consts::PI;
         }
+        while turn < -f32::consts::PI {
+            turn += 2.0 * f32::consts::PI;
+        }
+        let max_turn = INSPECTOR_TURN_RATE * dt;
+        inspector.facing += turn.max(-max_turn).min(max_turn);
     }
 
-    if not_too_close {
-        game.minions.push(new_minion);
+    for x in 0..game.dead_minions.len() {
+        if inspector_can_see(&game.inspector, &game.dead_minions[x].pos) {
+            game.caught = true;
+        }
+    }
+    if game.carrying && inspector_can_see(&game.inspector, &game.player.pos) {
+        game.caught = true;
     }
-    return not_too_close;
 }
 
-fn update_player_position(game: &mut MainState, dt: f32) {
+fn update_player_position(game: &mut MainState, dt: f32, speed: f32) {
+    let max_vel = MAX_PHYSICS_VEL * speed;
     let norm_sq = game.player.velocity.norm_squared();
-    if norm_sq > MAX_PHYSICS_VEL.powi(2) {
-        game.player.velocity = game.player.velocity / norm_sq.sqrt() * MAX_PHYSICS_VEL;
+    if norm_sq > max_vel.powi(2) {
+        game.player.velocity = game.player.velocity / norm_sq.sqrt() * max_vel;
     }
 
     game.player.pos += game.player.velocity*dt; // + 0.5*actor.accel*dt.powi(2);
 
-    if game.player.pos.x > (game.screen_width/2) as f32
+    // A map with walls keeps the lord in by itself, otherwise the screen edge does
+    let off_screen = game.player.pos.x > (game.screen_width/2) as f32
     || game.player.pos.x < -1.0*(game.screen_width/2) as f32
     || game.player.pos.y > (game.screen_height/2) as f32
-    || game.player.pos.y < -1.0*(game.screen_height/2) as f32{
+    || game.player.pos.y < -1.0*(game.screen_height/2) as f32;
+    if (off_screen && game.level.collision.is_none()) || game.level.blocked(&game.player.pos) {
         game.player.pos -= game.player.velocity*dt;
         game.player.velocity = Vector2::zeros();
     }
+
+    if game.player.velocity.x > 0.0 {
+        game.player.facing = 1.0;
+    }
+    else if game.player.velocity.x < 0.0{
+        game.player.facing = -1.0;
+    }
+}
+
+/// How heavy a body is to drag around. Bigger minions weigh more.
+fn body_weight(body: &Actor) -> f32 {
+    body.scale.x * 2.0
+}
+
+/// Fraction of full speed the lord moves at with what he is carrying
+fn carry_speed(game: &MainState) -> f32 {
+    let mut weight = 0.0;
+    for x in 0..game.dead_minions.len() {
+        if game.dead_minions[x].carried {
+            weight += body_weight(&game.dead_minions[x]);
+        }
+    }
+    1.0 / (1.0 + CARRY_SLOWDOWN * weight)
+}
+
+/// Where the n-th carried body hangs off the lord's hand
+fn hand_anchor(player: &Actor, n: usize) -> Point2<f32> {
+    player.pos + Vector2::new(35.0 * player.facing, 10.0 + 12.0 * n as f32)
+}
+
+/// Picks up the closest body in reach, if there's room for it
+fn pick_up_body(game: &mut MainState) {
+    let mut held = 0;
+    let mut closest: Option<usize> = None;
+    let mut closest_dist = PICKUP_RANGE;
+    for x in 0..game.dead_minions.len() {
+        if game.dead_minions[x].carried {
+            held += 1;
+            continue;
+        }
+        let dist = na::distance(&game.player.pos, &game.dead_minions[x].pos);
+        if dist < closest_dist {
+            closest_dist = dist;
+            closest = Some(x);
+        }
+    }
+    if held >= CARRY_LIMIT {
+        return;
+    }
+    if let Some(x) = closest {
+        game.dead_minions[x].carried = true;
+    }
+}
+
+/// Puts down the last body picked up. Dropped next to a fire pit or a
+/// hiding spot with room left, the body goes in there instead.
+fn drop_body(game: &mut MainState) {
+    let mut last: Option<usize> = None;
+    for x in 0..game.dead_minions.len() {
+        if game.dead_minions[x].carried {
+            last = Some(x);
+        }
+    }
+    if let Some(x) = last {
+        game.dead_minions[x].carried = false;
+        game.dead_minions[x].pos = game.player.pos + Vector2::new(10.0,-10.0);
+        let fire_range = SITE_RANGE * game.rounds.upgrades.fire_range_scale();
+        for site in game.sites.iter_mut() {
+            let range = if site.kind == SiteKind::FirePit { fire_range } else { SITE_RANGE };
+            if na::distance(&site.actor.pos, &game.dead_minions[x].pos) < range
+            && site.bodies.len() < site.capacity {
+                let body = game.dead_minions.remove(x);
+                let burn_time = site.burn_time;
+                site.bodies.push((body, burn_time));
+                break;
+            }
+        }
+    }
+}
+
+/// Burns down bodies in the fire pits and rolls for hidden bodies being
+/// found. A found body is thrown back out where everyone can see it.
+fn update_disposal_sites(game: &mut MainState, dt: f32) {
+    for x in 0..game.sites.len() {
+        match game.sites[x].kind {
+            SiteKind::FirePit => {
+                for body in game.sites[x].bodies.iter_mut() {
+                    body.1 -= dt;
+                }
+                let burned = game.sites[x].bodies.iter().filter(|body| body.1 <= 0.0).count();
+                game.sites[x].bodies.retain(|body| body.1 > 0.0);
+                for _ in 0..burned {
+                    achieve(game, GameEvent::BodyBurned);
+                }
+            }
+            SiteKind::Hide => {
+                if game.sites[x].bodies.len() == 0 {
+                    continue;
+                }
+                let site_pos = game.sites[x].actor.pos;
+                let mut find_rate = game.sites[x].find_rate;
+                let mut searched = na::distance(&game.inspector.pos, &site_pos) < HIDE_SEARCH_RANGE;
+                for y in 0..game.minions.len() {
+                    if na::distance(&minion_anchor(&game.minions[y]), &site_pos) < HIDE_SEARCH_RANGE {
+                        searched = true;
+                    }
+                }
+                if searched {
+                    find_rate *= HIDE_SEARCH_FACTOR;
+                }
+                if game.rng.gen::<f32>() < find_rate * dt {
+                    let (mut body, _) = game.sites[x].bodies.remove(0);
+                    body.pos = site_pos + Vector2::new(0.0,-40.0);
+                    game.dead_minions.push(body);
+                    game.scoring.penalty(Penalty::BodyFound);
+                }
+            }
+        }
+    }
+}
+/// The intermission shop, over the courtyard
+fn draw_shop(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let center = Point::new((game.screen_width / 2) as f32, (game.screen_height / 2) as f32);
+    graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.85))?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(center.x, center.y, 520.0, 300.0))?;
+    graphics::set_color(ctx, graphics::BLACK)?;
+    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+    let mut lines = vec![
+        format!("Round {} Cleared! {} Points To Spend", game.rounds.round,
+                game.rounds.wallet(game.scoring.total)),
+        String::new(),
+    ];
+    for x in 0..UPGRADES.len() {
+        let upgrade = UPGRADES[x];
+        let level = game.rounds.upgrades.level(upgrade);
+        let price = if level >= MAX_UPGRADE_LEVEL {
+            "Maxed".to_string()
+        }
+        else {
+            format!("{} Points", upgrade.cost(level))
+        };
+        let marker = if x == game.rounds.choice { "> " } else { "" };
+        lines.push(format!("{}{} (Level {}) - {}", marker, upgrade.name(), level, price));
+    }
+    let marker = if game.rounds.choice == UPGRADES.len() { "> " } else { "" };
+    lines.push(format!("{}Start Round {} ({} Friends)", marker, game.rounds.round + 1,
+                       Rounds::quota_for(game.rounds.round + 1)));
+    for x in 0..lines.len() {
+        if lines[x].is_empty() {
+            continue;
+        }
+        let mut line_text = graphics::Text::new(ctx, &lines[x], font)?;
+        let line_dest = Point::new(center.x - 240.0 + (line_text.width() / 2) as f32,
+                                   center.y - 120.0 + 30.0 * x as f32);
+        draw_text(ctx, &mut line_text, line_dest)?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)?;
+    Ok(())
+}
+
+/// With the wandering fire mutator, every fire pit jumps somewhere new
+/// every `FIRE_MOVE_TIME` seconds, taking whatever is burning with it
+fn move_fire_pits(game: &mut MainState, dt: f32) {
+    if !game.mutators.moving_fire {
+        return;
+    }
+    game.fire_move_timer -= dt;
+    if game.fire_move_timer > 0.0 {
+        return;
+    }
+    game.fire_move_timer = FIRE_MOVE_TIME;
+    for x in 0..game.sites.len() {
+        if game.sites[x].kind == SiteKind::FirePit {
+            game.sites[x].actor.pos = game.level.random_spawn_point(&mut game.rng);
+        }
+    }
 }
 /// **********************************************************************
 /// Actor Drawing
@@ -465,15 +1497,22 @@ fn draw_actor(assets: &mut Assets,
     let px = pos.x as f32;
     let py = pos.y as f32;
     let dest_point = graphics::Point::new(px,py);
+    let tint = if actor.tag == ActorType::Minion {
+        assets.archetype_images[actor.archetype as usize].1
+    }
+    else {
+        graphics::WHITE
+    };
     let image = assets.actor_image(actor);
     let rotation = 0.0;
     let pos_scale = (world_coords.1 *5)as f32 /(actor.pos.y+(world_coords.1 as f32 *5.5));
     let mut scale = actor.scale;
-    if actor.tag == ActorType::Minion || actor.tag == ActorType::DeadMinion || actor.tag == ActorType::Attention || actor.tag == ActorType::BodyReminder{
+    if actor.tag == ActorType::Minion || actor.tag == ActorType::DeadMinion || actor.tag == ActorType::Attention || actor.tag == ActorType::BodyReminder || actor.tag == ActorType::Inspector{
         scale.x *= pos_scale;
         scale.y *= pos_scale;
     }
 
+    graphics::set_color(ctx, tint)?;
     graphics::draw_ex(ctx, 
         image, 
         graphics::DrawParam{
@@ -482,7 +1521,46 @@ fn draw_actor(assets: &mut Assets,
             scale: scale,
             ..Default::default()
         }
-    )
+    )?;
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Draws the level background and its obstacles
+fn draw_level(assets: &mut Assets, ctx: &mut Context, level: &Level, world_coords: (u32, u32)) -> GameResult<()> {
+    let (screen_w, screen_h) = world_coords;
+    if let Some(ref background) = assets.background_image {
+        graphics::draw(ctx, background, Point::new((screen_w/2) as f32,(screen_h/2) as f32),0.0)?;
+    }
+    for &(tileset, param) in assets.map_tiles.iter() {
+        graphics::draw_ex(ctx, &assets.map_tilesets[tileset], param)?;
+    }
+    graphics::set_color(ctx, Color::new(0.45, 0.45, 0.5, 1.0))?;
+    for obstacle in level.obstacles.iter() {
+        // Rectangles are drawn around their centre
+        let center = world_to_screen_coords(screen_w, screen_h, obstacle.center());
+        graphics::rectangle(ctx, DrawMode::Fill,
+                            graphics::Rect::new(center.x, center.y, obstacle.w, obstacle.h))?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Draws the inspector's vision cone as a translucent fan
+fn draw_vision_cone(ctx: &mut Context, inspector: &Actor, world_coords: (u32, u32)) -> GameResult<()> {
+    let (screen_w, screen_h) = world_coords;
+    let segments = 8;
+    let mut points = vec![];
+    let eye = world_to_screen_coords(screen_w, screen_h, inspector.pos);
+    points.push(Point::new(eye.x, eye.y));
+    for i in 0..(segments + 1) {
+        let angle = inspector.facing - INSPECTOR_SIGHT_ANGLE
+            + 2.0 * INSPECTOR_SIGHT_ANGLE * (i as f32) / (segments as f32);
+        let edge = world_to_screen_coords(screen_w, screen_h,
+                                          inspector.pos + vec_from_angle(angle) * INSPECTOR_SIGHT_RANGE);
+        points.push(Point::new(edge.x, edge.y));
+    }
+    graphics::set_color(ctx, Color::new(1.0, 0.9, 0.2, 0.3))?;
+    graphics::polygon(ctx, DrawMode::Fill, &points)?;
+    graphics::set_color(ctx, graphics::WHITE)
 }
 
 fn draw_text(ctx: &mut Context, text: &mut graphics::Text, dest_point: Point) -> GameResult<()> {
@@ -503,15 +1581,9 @@ fn draw_player(assets: &mut Assets, ctx: &mut Context,
     let px = pos.x as f32;
     let py = pos.y as f32;
     let dest_point = graphics::Point::new(px,py);
+    let tint = assets.player_tint;
     let mut image;
 
-    if player.velocity.x > 0.0 {
-        player.facing = 1.0;
-    }
-    else if player.velocity.x < 0.0{
-        player.facing = -1.0;
-    }
-
     if player.velocity != Vector2::zeros(){
         if player.life >= 0.0 && player.life < 0.25 {
             if player.facing == 1.0{
@@ -560,6 +1632,7 @@ fn draw_player(assets: &mut Assets, ctx: &mut Context,
     let mut scale = player.scale;
     scale.x *= pos_scale;
     scale.y *= pos_scale;
+    graphics::set_color(ctx, tint)?;
     graphics::draw_ex(ctx, 
         image, 
         graphics::DrawParam{
@@ -568,7 +1641,16 @@ fn draw_player(assets: &mut Assets, ctx: &mut Context,
             scale: scale,
             ..Default::default()
         }
-    )
+    )?;
+    graphics::set_color(ctx, graphics::WHITE)
+}
+
+/// Things that get depth sorted when drawing the floor
+enum Depth {
+    Player,
+    Inspector,
+    Minion(usize),
+    DeadMinion(usize),
 }
 
 /// Translates the game coordinate system, with Y point up
@@ -582,11 +1664,37 @@ fn world_to_screen_coords(screen_width: u32, screen_height: u32, point: Point2<f
     Point2::new(x, y)
 }
 
+/// The other way round, for turning mouse positions into the world
+fn screen_to_world_coords(screen_width: u32, screen_height: u32, x: i32, y: i32) -> Point2<f32> {
+    let width = screen_width as f32;
+    let height = screen_height as f32;
+    Point2::new(x as f32 - width / 2.0, height / 2.0 - y as f32)
+}
+
 /// ********************************************************************
 /// Event Handler
 /// ********************************************************************
 impl EventHandler for MainState {
     fn update(&mut self, ctx: &mut Context, dt: Duration) -> GameResult<()> {
+        if self.quit_requested {
+            // Write the run out, then quit for real
+            self.quit_requested = false;
+            self.suspend_written = true;
+            self.state = pause::run_state(self);
+            // There's nothing to keep of the tutorial
+            if self.tutorial.is_none() {
+                let run = suspend::capture(self);
+                if let Err(e) = suspend::save_suspended(ctx, self.profile_id, &run) {
+                    println!("Could not suspend the run");
+                    println!("Error: {}", e);
+                }
+            }
+            if self.profile_dirty {
+                save_current_profile(self, ctx);
+            }
+            ctx.quit()?;
+            return Ok(());
+        }
         let time_passed = timer::duration_to_f64(dt) as f32;
         self.update_ui(ctx);
 
@@ -596,14 +1704,30 @@ impl EventHandler for MainState {
                 if self.attention.life == 1.0 && self.input.fire {
                     self.state = 1;
                     self.input.fire = false;
-                    self.rings[0].scale = Point::new(1.0,1.0);
-                    let shrink_speed = self.rng.gen_range(1.2,2.3);
-                    self.rings[0].velocity = Vector2::new(shrink_speed, shrink_speed);
-                    let goal_scale = self.rng.gen_range(0.2, 0.8);
-                    self.rings[1].scale = Point::new(goal_scale,goal_scale);
+                    // Minions placed in the editor can have their ring or
+                    // minigame set by hand
+                    let mut ring = None;
+                    let mut kind = None;
+                    self.partner = Archetype::Regular;
+                    for minion in self.minions.iter() {
+                        if na::distance(&self.player.pos, &minion_anchor(minion)) < 50.0 {
+                            ring = minion.ring;
+                            kind = minion.minigame;
+                            self.partner = minion.archetype;
+                            break;
+                        }
+                    }
+                    let base = current_difficulty(self);
+                    self.difficulty = self.rounds.upgrades.apply(self.partner.apply(base));
+                    if self.tutorial.is_some() {
+                        self.difficulty.ring_passes = 1;
+                    }
+                    self.high_five_kind = kind;
+                    self.high_five_ring = ring;
+                    start_high_five(self);
                 }
-                if self.timer <= 0.0 {
-                    self.state = 9;
+                if (self.mode.timed() && self.timer <= 0.0) || self.caught {
+                    end_run(self, ctx);
                 }
             }
             1 => {
@@ -616,33 +1740,55 @@ impl EventHandler for MainState {
                                 break;
                             }
                         }
-                        let mut succeeded = false;
-                        while !succeeded{
-                            succeeded = add_minion(self);
-                        }
                         self.attention.life = 0.0;
                         self.state = 2;       
-                        self.score += 1;
-                        if self.score < 10{
-                            self.timer += 5.0;
+                        if let Some(judgement) = self.judgement {
+                            let clean = floor_is_clean(self);
+                            self.scoring.high_five(self.partner, &judgement, clean);
                         }
-                        else {
-                            self.timer += 3.0;
+                        self.friends += 1;
+                        self.rounds.friends += 1;
+                        if let Some(judgement) = self.judgement {
+                            let event = GameEvent::HighFive {
+                                tier: Tier::from_judgement(&judgement),
+                                multiplier: self.scoring.multiplier(),
+                                friends: self.friends,
+                                total_friends: self.profile.total_friends,
+                            };
+                            achieve(self, event);
+                        }
+                        if self.mode.time_bonus() {
+                            self.timer += self.difficulty.time_bonus;
+                        }
+                        self.difficulty = current_difficulty(self);
+                        populate(self);
+                        if let Some(goal) = self.level.friends_goal {
+                            if self.friends >= goal && self.tutorial.is_none() {
+                                self.won = true;
+                                end_run(self, ctx);
+                            }
                         }
                     }
                     2 => {
                         for x in 0..self.minions.len(){
                             if na::distance(&self.player.pos,&(self.minions[x].pos+Vector2::new(75.0,0.0))) < 50.0{
-                                self.dead_minions.push(create_dead_minion(self.minions[x].pos.x, self.minions[x].pos.y));
+                                // In Zen the minion just walks off, and in
+                                // the tutorial unless it asked for a miss
+                                if tutorial::miss_kills(self) {
+                                    self.dead_minions.push(create_dead_minion(self.minions[x].pos.x, self.minions[x].pos.y));
+                                    self.kills += 1;
+                                }
                                 self.minions.remove(x);
                                 break;
                             }
                         }
-                        let mut succeeded = false;
-                        while !succeeded{
-                            succeeded = add_minion(self);
-                        }
+                        self.scoring.miss();
+                        achieve(self, GameEvent::Miss);
+                        populate(self);
                         self.state = 0;
+                        if self.mode.out_of_lives(self.kills) && self.tutorial.is_none() {
+                            end_run(self, ctx);
+                        }
                     }
                     _ => (),
                 }
@@ -654,64 +1800,169 @@ impl EventHandler for MainState {
                 if self.success_five.life == 0.0 {
                     self.success_five.life = SUCCESS_LIFE;
                     self.state = 0;
+                    // Making the quota stops the clock and opens the shop
+                    if self.mode.rounds() && self.rounds.quota_met() {
+                        self.state = 11;
+                    }
+                }
+                if self.caught {
+                    end_run(self, ctx);
                 }
             }
             3 => {
-                let mut succeeded = false;
-                for _x in 0..3{
-                    while !succeeded{
-                        succeeded = add_minion(self);
-                    }
-                    succeeded = false;
-                 }
-                self.state = 4;
+                self.state = 12;
             }
             4 => {
-                if self.input.any_key {
-                    self.state = 5;
-                    self.input.any_key = false;
+                // Title screen, pick a mode, or carry on with the
+                // suspended run listed before them
+                let offset = if self.suspended.is_some() { 1 } else { 0 };
+                let entries = GAME_MODES.len() + offset;
+                let presses = self.input.presses.clone();
+                for press in presses.iter() {
+                    match *press {
+                        Press::Up => {
+                            self.mode_choice = (self.mode_choice + entries - 1) % entries;
+                        }
+                        Press::Down => {
+                            self.mode_choice = (self.mode_choice + 1) % entries;
+                        }
+                        Press::Left | Press::Right => {
+                            let step = if *press == Press::Left { LEVELS.len() - 1 } else { 1 };
+                            let mut choice = (self.profile.level + step) % LEVELS.len();
+                            while !self.profile.unlocked(Unlock::Level(choice)) {
+                                choice = (choice + step) % LEVELS.len();
+                            }
+                            self.profile.level = choice;
+                        }
+                        Press::Fire => {
+                            // A run is only continued once, and starting
+                            // a new one gives up on it
+                            if let Some(run) = self.suspended.take() {
+                                if let Err(e) = suspend::delete_suspended(ctx, self.profile_id) {
+                                    println!("Could not delete the suspended run");
+                                    println!("Error: {}", e);
+                                }
+                                if self.mode_choice == 0 {
+                                    suspend::restore(self, ctx, run)?;
+                                    break;
+                                }
+                            }
+                            self.mode = GAME_MODES[self.mode_choice - offset];
+                            let index = self.profile.level;
+                            start_run(self, ctx, index)?;
+                            save_current_profile(self, ctx);
+                            self.state = 0;
+                            if !self.profile.tutorial_done && !self.profile.settings.skip_intro {
+                                tutorial::start_tutorial(self);
+                            }
+                            break;
+                        }
+                    }
                 }
-            }
-            5 => {
-                if self.input.any_key {
-                    self.state = 6;
-                    self.input.any_key = false;
+                if self.assets.skin != self.profile.skin {
+                    let skin = self.profile.skin;
+                    self.assets.set_skin(skin);
                 }
+                self.input.any_key = false;
             }
-            6 => {
-                if self.input.any_key {
-                    self.state = 7;
-                    self.input.any_key = false;
-                }
+            9 => {
+
             }
-            7 => {
-                if self.input.any_key {
-                    self.state = 8;
-                    self.input.any_key = false;
-                }
+            12 => {
+                picker::picker_update(self, ctx)?;
             }
-            8 => {
-                if self.input.any_key {
-                    self.state = 0;
-                    self.input.any_key = false;
+            14 => {
+                pause::pause_update(self, ctx)?;
+            }
+            11 => {
+                // Intermission shop, the last entry starts the next round
+                let entries = UPGRADES.len() + 1;
+                let presses = self.input.presses.clone();
+                for press in presses.iter() {
+                    match *press {
+                        Press::Up => {
+                            self.rounds.choice = (self.rounds.choice + entries - 1) % entries;
+                        }
+                        Press::Down => {
+                            self.rounds.choice = (self.rounds.choice + 1) % entries;
+                        }
+                        Press::Fire => {
+                            if self.rounds.choice < UPGRADES.len() {
+                                let upgrade = UPGRADES[self.rounds.choice];
+                                self.rounds.buy(upgrade, self.scoring.total);
+                            }
+                            else {
+                                let start_time = self.mode.start_time(self.level.timer);
+                                self.timer = self.rounds.next_round(start_time);
+                                self.state = 0;
+                                break;
+                            }
+                        }
+                        _ => (),
+                    }
                 }
+                self.input.any_key = false;
+                self.input.fire = false;
             }
-            9 => {
-
+            10 => {
+                if self.editor.save_requested {
+                    self.editor.save_requested = false;
+                    if let Err(e) = editor::save_edited_level(self, ctx) {
+                        self.editor.message = format!("Could not save level: {}", e);
+                    }
+                }
             }
             _ => (),
         }
 
-        if self.state < 3 {
+        if self.tutorial.is_some() && self.state < 3 {
+            tutorial::tutorial_update(self, ctx, time_passed)?;
+        }
+        if self.state < 3 && self.mode.timed() && self.tutorial.is_none() {
             self.timer -= time_passed;
         }
+        for event in self.scoring.events.drain(..) {
+            self.score_popups.push((event.describe(), SCORE_POPUP_LIFE));
+        }
+        for popup in self.score_popups.iter_mut() {
+            popup.1 -= time_passed;
+        }
+        self.score_popups.retain(|popup| popup.1 > 0.0);
+        for toast in self.toasts.iter_mut() {
+            toast.1 -= time_passed;
+        }
+        self.toasts.retain(|toast| toast.1 > 0.0);
+        if self.profile_dirty {
+            save_current_profile(self, ctx);
+        }
+        self.input.presses.clear();
 
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx);
-        
+
+        // A paused run is drawn as it was, under the menus
+        let scene = pause::run_state(self);
+        match scene {
+            0 | 10 | 11 => self.draw_floor(ctx)?,
+            _ => (),
+        }
+        if scene == 10 {
+            editor::draw_editor(self, ctx)?;
+        }
+        if scene == 11 {
+            draw_shop(self, ctx)?;
+        }
+        if scene == 12 || scene == 13 {
+            let center = Point::new((self.screen_width / 2) as f32, (self.screen_height / 2) as f32);
+            graphics::draw(ctx, &self.assets.start1_image, center, 0.0)?;
+        }
+        if scene == 12 {
+            picker::draw_picker(self, ctx)?;
+        }
+
         {
             let assets = &mut self.assets;
             let coords = (self.screen_width, self.screen_height);
@@ -721,46 +1972,20 @@ impl EventHandler for MainState {
             let timer_dest = Point::new(self.screen_width as f32 - 200.0 + (self.timer_display.width()/2) as f32 ,
                                                     20.0);
 
-            match self.state{
-                0 => {
-                    let player = &mut self.player;
-
-                    for x in 0..self.minions.len(){
-                        draw_actor(assets,ctx,&self.minions[x],coords)?;                    
-                    }
-
-                    draw_actor(assets,ctx,&self.fire,coords)?;
-
-                    if !self.input.fire {
-                        for x in 0..self.dead_minions.len(){
-                            draw_actor(assets,ctx,&self.dead_minions[x],coords)?;                    
-                        }
-                    }
-
-                    draw_player(assets,ctx,player,coords)?;
-
-                    if self.input.fire {
-                        for x in 0..self.dead_minions.len(){
-                            draw_actor(assets,ctx,&self.dead_minions[x],coords)?;                    
-                        }
-                    }
-
-                    if self.attention.life == 1.0 {
-                        draw_actor(assets,ctx, &self.attention,coords)?;
-                    }
-                    if self.body_reminder.life == 1.0 {
-                        draw_actor(assets,ctx, &self.body_reminder,coords)?;
-                    }
-
-
-                }
+            match scene {
                 1 => {
-                    draw_actor(assets,ctx,&self.rings[0],coords)?;
-                    draw_actor(assets,ctx,&self.rings[1],coords)?;
+                    self.minigame.draw(ctx,assets,coords)?;
+                    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+                    let mut name_text = graphics::Text::new(ctx, self.minigame.name(), font)?;
+                    let name_dest = Point::new((self.screen_width/2) as f32, 60.0);
+                    graphics::set_color(ctx, graphics::BLACK)?;
+                    draw_text(ctx, &mut name_text, name_dest)?;
+                    graphics::set_color(ctx, graphics::WHITE)?;
                 }
                 2 => {
                     let player = &self.player;
 
+                    draw_level(assets,ctx,&self.level,coords)?;
                     draw_actor(assets,ctx,player,coords)?;
                     draw_actor(assets,ctx,&self.success_five,coords)?;                
                 }
@@ -769,117 +1994,354 @@ impl EventHandler for MainState {
                 }
                 4 => {
                     graphics::draw(ctx, &assets.start1_image, Point::new((coords.0/2) as f32,(coords.1/2) as f32),0.0)?;
-                }
-                5 => {
-                    graphics::draw(ctx, &assets.start2_image, Point::new((coords.0/2) as f32,(coords.1/2) as f32),0.0)?;
-                }
-                6 => {
-                    graphics::draw(ctx, &assets.start3_image, Point::new((coords.0/2) as f32,(coords.1/2) as f32),0.0)?;
-                }
-                7 => {
-                    graphics::draw(ctx, &assets.start4_image, Point::new((coords.0/2) as f32,(coords.1/2) as f32),0.0)?;
-                }
-                8 => {
-                    graphics::draw(ctx, &assets.controls_image, Point::new((coords.0/2) as f32,(coords.1/2) as f32),0.0)?;
+                    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+                    let mut entries = vec![];
+                    if let Some(ref run) = self.suspended {
+                        entries.push(("Continue".to_string(), run.describe(), String::new()));
+                    }
+                    for mode in GAME_MODES.iter() {
+                        let best = mode_best(&self.profile.high_scores, &self.profile.daily_results, self.today, *mode);
+                        entries.push((mode.name().to_string(), mode.description().to_string(), best));
+                    }
+                    let top = (coords.1 as f32) - 190.0 - 30.0 * (entries.len() - GAME_MODES.len()) as f32;
+                    for x in 0..entries.len() {
+                        let (ref name, ref description, ref best) = entries[x];
+                        let line = if x == self.mode_choice {
+                            format!("> {} - {}{}", name, description, best)
+                        }
+                        else {
+                            format!("{}{}", name, best)
+                        };
+                        let mut mode_text = graphics::Text::new(ctx, &line, font)?;
+                        let mode_dest = Point::new(60.0 + (mode_text.width() / 2) as f32,
+                                                   top + 30.0 * x as f32);
+                        graphics::set_color(ctx, graphics::BLACK)?;
+                        draw_text(ctx, &mut mode_text, mode_dest)?;
+                    }
+                    // Mutators along the top, toggled with the number keys
+                    for x in 0..MUTATOR_COUNT {
+                        let line = format!("{} [{}] {}", x + 1,
+                                           if self.mutators.is_on(x) { "x" } else { " " },
+                                           Mutators::name(x));
+                        let mut mutator_text = graphics::Text::new(ctx, &line, font)?;
+                        let mutator_dest = Point::new(60.0 + (mutator_text.width() / 2) as f32,
+                                                      40.0 + 25.0 * x as f32);
+                        graphics::set_color(ctx, graphics::BLACK)?;
+                        draw_text(ctx, &mut mutator_text, mutator_dest)?;
+                    }
+                    // The daily challenge is the same for everyone, so it's played without
+                    let offset = if self.suspended.is_some() { 1 } else { 0 };
+                    let daily = self.mode_choice >= offset && GAME_MODES[self.mode_choice - offset].daily();
+                    let multiplier = if daily {
+                        "Mutators Are Off In The Daily Challenge".to_string()
+                    }
+                    else {
+                        format!("Score x{:.2}", self.mutators.score_multiplier())
+                    };
+                    let mut multiplier_text = graphics::Text::new(ctx, &multiplier, font)?;
+                    let multiplier_dest = Point::new(60.0 + (multiplier_text.width() / 2) as f32,
+                                                     40.0 + 25.0 * MUTATOR_COUNT as f32);
+                    draw_text(ctx, &mut multiplier_text, multiplier_dest)?;
+                    // What the profile has unlocked, and what's next
+                    let next = match self.profile.next_unlock() {
+                        Some(unlock) => format!(", {} At {}", unlock.name(), unlock.friends()),
+                        None => ", Everything Unlocked".to_string(),
+                    };
+                    let unlock_lines = [
+                        format!("< {} > Level", LEVELS[self.profile.level].name),
+                        format!("Tab: {}", self.profile.skin.info().name),
+                        format!("{} Friends Made{}", self.profile.total_friends, next),
+                        format!("{}/{} Achievements", self.profile.achievements.len(), ACHIEVEMENTS.len()),
+                    ];
+                    for x in 0..unlock_lines.len() {
+                        let mut unlock_text = graphics::Text::new(ctx, &unlock_lines[x], font)?;
+                        let unlock_dest = Point::new(60.0 + (unlock_text.width() / 2) as f32,
+                                                     40.0 + 25.0 * (MUTATOR_COUNT + 2 + x) as f32);
+                        draw_text(ctx, &mut unlock_text, unlock_dest)?;
+                    }
+                    graphics::set_color(ctx, graphics::WHITE)?;
                 }
                 9 => {
                     graphics::draw(ctx, &assets.end_screen_image, Point::new((coords.0/2) as f32,(coords.1/2) as f32),0.0)?;
                     let font = &mut graphics::Font::new(ctx, "/OpenSans-ExtraBold.ttf", 32).unwrap();
-                    let end_str1 = format!("Congratulations, You Have Made {} Friends", self.score);
+                    let end_str1 = if self.caught {
+                        format!("The Inspector Caught You After {} Friends", self.friends)
+                    }
+                    else if self.won {
+                        format!("{} Cleared, You Have Made {} Friends", self.level.name, self.friends)
+                    }
+                    else {
+                        format!("Congratulations, You Have Made {} Friends", self.friends)
+                    };
                     let mut end_text1 = graphics::Text::new(ctx, &end_str1, font).unwrap();
                     let end_dest1 = Point::new((self.screen_width/2) as f32 - 100.0,
                                                     (self.screen_height/2) as f32);
-                    let end_str2 = format!("Press Esc To Exit Game");
+                    let end_str2 = if self.mode.daily() && self.daily_best {
+                        format!("New Best For Today! Press Esc To Exit Game")
+                    }
+                    else {
+                        match self.high_score_place {
+                            Some(place) => format!("New {} High Score, Number {}! Press Esc To Exit Game",
+                                                   self.mode.name(), place + 1),
+                            None => format!("{}{}. Press Esc To Exit Game", self.mode.name(),
+                                            mode_best(&self.profile.high_scores, &self.profile.daily_results, self.today, self.mode)),
+                        }
+                    };
                     let mut end_text2 = graphics::Text::new(ctx, &end_str2, font).unwrap();
                     let end_dest2 = Point::new((self.screen_width/2) as f32 - 100.0,
+                                                     (self.screen_height/2) as f32 + 100.0);
+                    let end_str3 = format!("{} Minions Saw Too Much", self.witnesses);
+                    let mut end_text3 = graphics::Text::new(ctx, &end_str3, font).unwrap();
+                    let end_dest3 = Point::new((self.screen_width/2) as f32 - 100.0,
                                                      (self.screen_height/2) as f32 + 50.0);
                     graphics::set_color(ctx, graphics::BLACK)?;
                     draw_text(ctx, &mut end_text1, end_dest1)?;
                     draw_text(ctx, &mut end_text2, end_dest2)?;
+                    draw_text(ctx, &mut end_text3, end_dest3)?;
+
+                    // Where the points came from, down the left
+                    let small_font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+                    let mut lines = self.scoring.breakdown();
+                    if self.mode.rounds() {
+                        lines.push(format!("Reached Round {}", self.rounds.round));
+                    }
+                    for unlock in self.new_unlocks.iter() {
+                        lines.push(format!("Unlocked {}!", unlock.name()));
+                    }
+                    for x in 0..lines.len() {
+                        let mut line_text = graphics::Text::new(ctx, &lines[x], small_font)?;
+                        let line_dest = Point::new(60.0 + (line_text.width() / 2) as f32,
+                                                   (self.screen_height/2) as f32 + 150.0 + 25.0 * x as f32);
+                        draw_text(ctx, &mut line_text, line_dest)?;
+                    }
                     graphics::set_color(ctx, graphics::WHITE)?;
                 }
                 _ => (),
             }
 
-            if self.state < 3{
+            if scene < 3{
                 graphics::set_color(ctx, graphics::BLACK)?;
                 draw_text(ctx,&mut self.score_display, score_dest)?;
                 draw_text(ctx,&mut self.timer_display, timer_dest)?;
+                let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+                let popups = if self.profile.settings.score_popups { self.score_popups.len() } else { 0 };
+                for x in 0..popups {
+                    let mut popup_text = graphics::Text::new(ctx, &self.score_popups[x].0, font)?;
+                    let popup_dest = Point::new(200.0 + (popup_text.width() / 2) as f32,
+                                                score_dest.y + 25.0 * (x + 1) as f32);
+                    draw_text(ctx, &mut popup_text, popup_dest)?;
+                }
                 graphics::set_color(ctx, graphics::WHITE)?;
             }
+        }
+        if scene < 3 {
+            tutorial::draw_tutorial(self, ctx)?;
+        }
+        if self.state == 14 {
+            pause::draw_pause(self, ctx)?;
+        }
+        if self.state == 13 {
+            settings::draw_settings(self, ctx)?;
+        }
 
+        // Achievement toasts, stacked down from the top middle
+        if self.toasts.len() > 0 {
+            let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+            for x in 0..self.toasts.len() {
+                let mut toast_text = graphics::Text::new(ctx, &self.toasts[x].0, font)?;
+                let toast_dest = Point::new((self.screen_width / 2) as f32, 100.0 + 40.0 * x as f32);
+                graphics::set_color(ctx, Color::new(1.0, 0.9, 0.5, 0.9))?;
+                graphics::rectangle(ctx, DrawMode::Fill,
+                                    graphics::Rect::new(toast_dest.x, toast_dest.y,
+                                                        toast_text.width() as f32 + 30.0, 34.0))?;
+                graphics::set_color(ctx, graphics::BLACK)?;
+                draw_text(ctx, &mut toast_text, toast_dest)?;
+            }
+            graphics::set_color(ctx, graphics::WHITE)?;
         }
 
         graphics::present(ctx);
         Ok(())
     }
 
+    /// Quitting mid-run, which Escape does too, pauses. Quitting again
+    /// while paused holds off for a frame, so `update` can suspend the
+    /// run first. Only `update` gets the context.
+    fn quit_event(&mut self) -> bool {
+        let run_state = pause::run_state(self);
+        if suspend::in_run(run_state) && !self.suspend_written {
+            if run_state == self.state {
+                pause::open_pause(self);
+            }
+            else {
+                self.quit_requested = true;
+            }
+            return true;
+        }
+        false
+    }
+
+    fn focus_event(&mut self, gained: bool) {
+        if !gained {
+            pause::open_pause(self);
+        }
+    }
+
+    fn controller_button_down_event(&mut self, btn: Button, _instance_id: i32) {
+        if btn == Button::Start {
+            if self.state == 14 {
+                pause::resume(self);
+            }
+            else {
+                pause::open_pause(self);
+            }
+        }
+    }
+
     fn key_down_event(&mut self,
                       keycode: Keycode,
-                      _keymod: Mod,
-                      _repeat: bool) {
-        self.input.any_key = true;
-        match keycode {
-            Keycode::W => {
-                self.input.yaxis = 1.0;
+                      keymod: Mod,
+                      repeat: bool) {
+        if keycode == Keycode::F2 && !repeat {
+            match self.state {
+                0 => editor::enter_editor(self),
+                10 => editor::leave_editor(self),
+                _ => (),
             }
-            Keycode::S => {
-                self.input.yaxis = -1.0;
+            return;
+        }
+        if keycode == Keycode::Tab && self.state < 3 {
+            if let Some(ref mut tutorial) = self.tutorial {
+                tutorial.skipped = true;
+                return;
             }
-            Keycode::A => {
-                self.input.xaxis = -1.0;
+        }
+        if self.state == 10 {
+            editor::editor_key(self, keycode);
+            return;
+        }
+        if self.state == 12 {
+            picker::picker_key(self, keycode, keymod);
+            return;
+        }
+        if self.state == 13 {
+            settings::settings_key(self, keycode);
+            return;
+        }
+        if self.state == 4 && !repeat {
+            let mutator = match keycode {
+                Keycode::Num1 => Some(0),
+                Keycode::Num2 => Some(1),
+                Keycode::Num3 => Some(2),
+                Keycode::Num4 => Some(3),
+                Keycode::Num5 => Some(4),
+                Keycode::Num6 => Some(5),
+                _ => None,
+            };
+            if let Some(x) = mutator {
+                self.mutators.toggle(x);
+                return;
             }
-            Keycode::D => {
-                self.input.xaxis = 1.0;
+            if keycode == Keycode::Tab {
+                let current = SKINS.iter().position(|skin| *skin == self.profile.skin).unwrap_or(0);
+                let mut choice = (current + 1) % SKINS.len();
+                while !self.profile.unlocked(Unlock::Skin(SKINS[choice])) {
+                    choice = (choice + 1) % SKINS.len();
+                }
+                self.profile.skin = SKINS[choice];
+                return;
+            }
+        }
+        self.input.any_key = true;
+        // Everything else goes through the profile's key bindings
+        let action = match self.profile.bindings.action(keycode) {
+            Some(action) => action,
+            None => return,
+        };
+        if !repeat {
+            let press = match action {
+                Action::Up => Some(Press::Up),
+                Action::Down => Some(Press::Down),
+                Action::Left => Some(Press::Left),
+                Action::Right => Some(Press::Right),
+                Action::Fire => Some(Press::Fire),
+                _ => None,
+            };
+            if let Some(press) = press {
+                self.input.presses.push(press);
             }
-            Keycode::Up => {
+        }
+        match action {
+            Action::Up => {
                 self.input.yaxis = 1.0;
             }
-            Keycode::Down => {
+            Action::Down => {
                 self.input.yaxis = -1.0;
             }
-            Keycode::Left => {
+            Action::Left => {
                 self.input.xaxis = -1.0;
             }
-            Keycode::Right => {
+            Action::Right => {
                 self.input.xaxis = 1.0;
             }
-            Keycode::Space => {
+            Action::Fire => {
                 self.input.fire = true;
             }
-            _ => (), // Do nothing
+            Action::PickUp => {
+                if !repeat {
+                    self.input.pickup = true;
+                }
+            }
+            Action::Drop => {
+                if !repeat {
+                    self.input.drop = true;
+                }
+            }
         }
     }
 
 
     fn key_up_event(&mut self, keycode: Keycode, _keymod: Mod, _repeat: bool) {
         self.input.any_key = false;
-        match keycode {
-            Keycode::W | Keycode::S => {
+        match self.profile.bindings.action(keycode) {
+            Some(Action::Up) | Some(Action::Down) => {
                 self.input.yaxis = 0.0;
             }
-            Keycode::A | Keycode::D => {
+            Some(Action::Left) | Some(Action::Right) => {
                 self.input.xaxis = 0.0;
             }
-            Keycode::Up | Keycode::Down => {
-                self.input.yaxis = 0.0;
-            }
-            Keycode::Left | Keycode::Right => {
-                self.input.xaxis = 0.0;
-            }
-            Keycode::Space => {
+            Some(Action::Fire) => {
                 self.input.fire = false;
             }
             _ => (), // Do nothing
         }
     }
+
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: i32, y: i32) {
+        if self.state == 10 {
+            editor::editor_mouse_down(self, button, x, y);
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: i32, y: i32) {
+        if self.state == 10 {
+            editor::editor_mouse_up(self, button, x, y);
+        }
+    }
+
+    fn mouse_motion_event(&mut self, _state: MouseState, x: i32, y: i32, _xrel: i32, _yrel: i32) {
+        if self.state == 10 {
+            editor::editor_mouse_motion(self, x, y);
+        }
+    }
 }
 /// ********************************************************************
 /// State Updates
 /// ********************************************************************
 fn update0(game: &mut MainState, _ctx: &mut Context, dt: f32) {
-    player_handle_input(&mut game.player, &game.input, dt);
-    update_player_position(game, dt);
+    let speed = carry_speed(game) * game.rounds.upgrades.speed_scale();
+    player_handle_input(&mut game.player, &game.input, dt, speed, &game.mutators);
+    update_player_position(game, dt, speed);
     game.attention.pos = game.player.pos + Vector2::new(50.0,100.0);
     game.body_reminder.pos = game.player.pos + Vector2::new(120.0,100.0);
 
@@ -889,40 +2351,220 @@ fn update0(game: &mut MainState, _ctx: &mut Context, dt: f32) {
 
     //Detecting if player is close to minion
     for x in 0..game.minions.len() {
+        // Frightened minions want nothing to do with the lord
+        if game.minions[x].behaviour == Behaviour::Flee {
+            continue;
+        }
         if na::distance(&game.player.pos,&(game.minions[x].pos+Vector2::new(75.0,0.0))) < 50.0 && game.dead_minions.len() == 0{
             game.attention.life = 1.0;
             break;
         }
         if na::distance(&game.player.pos,&(game.minions[x].pos+Vector2::new(75.0,0.0))) < 50.0 && game.dead_minions.len() != 0{
-            game.body_reminder.life = 1.0;
+            if !game.mutators.no_body_reminder {
+                game.body_reminder.life = 1.0;
+            }
             break;
         }
     }
 
-    //Detecting if player is close to dead_minion
+    // Picking up and dropping bodies, dropping one by a fire or a hiding spot puts it in
+    if game.input.pickup {
+        pick_up_body(game);
+        game.input.pickup = false;
+    }
+    if game.input.drop {
+        drop_body(game);
+        game.input.drop = false;
+    }
+
+    // Carried bodies hang off the lord's hand
+    game.carrying = false;
+    let mut held = 0;
     for x in 0..game.dead_minions.len() {
-        if na::distance(&game.player.pos,&game.dead_minions[x].pos) < 70.0 && game.input.fire{
-            game.dead_minions[x].pos = game.player.pos + Vector2::new(10.0,10.0);
-            break;
+        if game.dead_minions[x].carried {
+            game.dead_minions[x].pos = hand_anchor(&game.player, held);
+            game.carrying = true;
+            held += 1;
         }
     }
-    // Burning the bodies
-    for x in 0..game.dead_minions.len() {
-        if na::distance(&game.fire.pos,&game.dead_minions[x].pos) < 50.0 && game.input.fire{
-            game.dead_minions.remove(x);
-            break;
+
+    update_disposal_sites(game, dt);
+    move_fire_pits(game, dt);
+    update_minions(game, dt);
+    if game.tutorial.is_none() {
+        update_inspector(game, dt);
+    }
+}
+
+/// The best score of a mode, for showing next to it. For the daily
+/// challenge that's today's best.
+fn mode_best(high_scores: &HighScores, daily: &DailyResults, today: u32, mode: GameMode) -> String {
+    if mode.daily() {
+        return match daily.best(today) {
+            Some(best) => format!(" (Today's Best {}, {})", best.score, best.code),
+            None => format!(" ({})", date_string(today)),
+        };
+    }
+    match high_scores.table(mode).first() {
+        Some(best) => format!(" (Best {})", best),
+        None => String::new(),
+    }
+}
+
+/// Whether there's no body lying around or stuffed somewhere it could
+/// be found. Bodies in a fire pit are as good as gone.
+fn floor_is_clean(game: &MainState) -> bool {
+    game.dead_minions.len() == 0
+    && game.sites.iter().all(|site| site.kind == SiteKind::FirePit || site.bodies.len() == 0)
+}
+
+/// Passes a gameplay event on to the achievement tracker, putting up a
+/// toast for anything earned for the first time. Nothing is earned in
+/// the tutorial.
+fn achieve(game: &mut MainState, event: GameEvent) {
+    if game.tutorial.is_some() {
+        return;
+    }
+    for achievement in game.achievements.handle(event) {
+        if game.profile.achievements.insert(achievement) {
+            game.toasts.push((format!("Achievement: {} - {}", achievement.name(), achievement.description()),
+                             TOAST_LIFE));
+            game.profile_dirty = true;
         }
     }
 }
 
+/// Starts a run of `game.mode` on one of the `LEVELS`, from a clean
+/// floor. The caller picks the state it opens in.
+fn start_run(game: &mut MainState, ctx: &mut Context, level_index: usize) -> GameResult<()> {
+    tutorial::end_tutorial(game);
+    switch_level(game, ctx, level_index)?;
+    game.minions.clear();
+    game.dead_minions.clear();
+    game.success_five = create_success_five();
+    game.carrying = false;
+    game.witnesses = 0;
+    game.caught = false;
+    game.won = false;
+    game.friends = 0;
+    game.kills = 0;
+    game.judgement = None;
+    game.skill = SkillTracker::default();
+    game.score_popups.clear();
+    game.fire_move_timer = FIRE_MOVE_TIME;
+    game.spawns = 0;
+    game.spawns_full = false;
+    game.minigames = 0;
+    // Everyone gets the same daily challenge
+    if game.mode.daily() {
+        game.mutators = Mutators::default();
+    }
+    game.scoring = Scoring::default();
+    game.scoring.mutator_multiplier = game.mutators.score_multiplier();
+    game.timer = game.mode.start_time(game.level.timer);
+    game.rounds = Rounds::default();
+    game.achievements = AchievementTracker::default();
+    if game.mode.rounds() {
+        game.timer = game.rounds.next_round(game.timer);
+    }
+    game.seed = if game.mode.daily() {
+        daily_seed(game.today)
+    }
+    else {
+        game.rng.gen()
+    };
+    spawn_starting_minions(game);
+    Ok(())
+}
+
+fn end_run(game: &mut MainState, ctx: &mut Context) {
+    game.state = 9;
+    let clean = floor_is_clean(game);
+    game.scoring.finish(clean);
+    let event = GameEvent::RunFinished {
+        friends: game.friends,
+        kills: game.kills,
+        deaths_possible: game.mode.deaths(),
+        clean: clean,
+    };
+    achieve(game, event);
+    game.new_unlocks = game.profile.record_run(game.friends, game.scoring.total);
+    if game.mode.daily() {
+        game.daily_best = game.profile.daily_results.record(game.today, game.scoring.total, game.friends);
+    }
+    else {
+        game.high_score_place = game.profile.high_scores.record(game.mode, game.scoring.total);
+    }
+    save_current_profile(game, ctx);
+}
+
+fn save_current_profile(game: &mut MainState, ctx: &mut Context) {
+    game.profile_dirty = false;
+    if let Err(e) = save_profile(ctx, game.profile_id, &game.profile) {
+        println!("Could not save the profile");
+        println!("Error: {}", e);
+    }
+}
+
+/// Switches to another profile, along with the level and skin it last
+/// played with
+fn select_profile(game: &mut MainState, ctx: &mut Context, id: u32) -> GameResult<()> {
+    if game.profile_dirty {
+        save_current_profile(game, ctx);
+    }
+    game.profile = load_profile(ctx, id);
+    game.profile_id = id;
+    game.profiles.last = Some(id);
+    game.suspended = suspend::load_suspended(ctx, id);
+    game.mode_choice = 0;
+    if let Err(e) = save_profile_index(ctx, &game.profiles) {
+        println!("Could not save the profile index");
+        println!("Error: {}", e);
+    }
+    if game.level_index != game.profile.level {
+        let index = game.profile.level;
+        switch_level(game, ctx, index)?;
+    }
+    if game.assets.skin != game.profile.skin {
+        let skin = game.profile.skin;
+        game.assets.set_skin(skin);
+    }
+    Ok(())
+}
+
+/// Rolls the minigame for the minion being high fived. The roll comes
+/// from `minigames`, so stepping that back and calling this again gives
+/// the same minigame over.
+fn start_high_five(game: &mut MainState) {
+    let mut rng = seeded_rng(game.seed, Stream::Minigame, game.minigames);
+    game.minigames += 1;
+    game.minigame = start_minigame(&mut rng, &game.difficulty, &game.mutators, game.high_five_kind, game.high_five_ring);
+}
+
+/// The difficulty curve at the friends made this run, adjusted to the
+/// player's skill when the mode allows. Friends rather than points, so
+/// bonuses don't rush it and penalties don't ease it off.
+fn current_difficulty(game: &MainState) -> Difficulty {
+    let base = game.level.difficulty.at(game.friends);
+    if game.mode.adaptive() {
+        game.skill.adjust(base)
+    }
+    else {
+        base
+    }
+}
+
 fn update1(game: &mut MainState, _ctx: &mut Context, dt: f32) -> u32 {
-    shrink_ring(&mut game.rings[0], dt);
-    if game.input.fire {
-        let ring_difference = game.rings[0].scale.x - game.rings[1].scale.x;
-        if ring_difference.abs() <= 0.1 {
+    if let Some(judgement) = game.minigame.update(&game.input.presses, dt) {
+        // The tutorial's slow ring says nothing about the player
+        if game.tutorial.is_none() {
+            game.skill.record(judgement.error, judgement.time, judgement.speed, judgement.window);
+        }
+        game.judgement = Some(judgement);
+        if judgement.hit() {
             return 1;
         }
-        else if ring_difference.abs() > 0.1 {
+        else {
             return 2;
         }
     }
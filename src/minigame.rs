@@ -0,0 +1,575 @@
+use ggez::{Context, GameResult};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point};
+use na::core::Vector2;
+use rand::Rng;
+
+use difficulty::{Difficulty, roll};
+use level::RingParams;
+use mutators::Mutators;
+use {Actor, Assets, create_ring, create_goal_ring, shrink_ring, draw_actor};
+
+/// ********************************************************************
+/// High Five Minigames
+///
+/// State 1 runs whichever minigame the minion being high fived asked
+/// for. Every minigame judges the attempt in the same units as the
+/// difficulty's hit window, so scoring and the skill tracker don't need
+/// to know which one was played. The shrinking ring is the default; the
+/// others come up more often as the difficulty's `variety` goes up.
+/// ********************************************************************
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MinigameKind {
+    /// Stop the shrinking ring on the goal ring
+    Ring,
+    /// Press the arrow keys shown, in order, before time runs out
+    Arrows,
+    /// Stop a sliding marker inside the target zone
+    SlidingBar,
+    /// Stop the shrinking ring on two goal rings, one after the other
+    DoubleRing,
+    /// Press along with a short pattern of beats
+    Rhythm,
+}
+
+pub const MINIGAME_KINDS: [MinigameKind; 5] = [
+    MinigameKind::Ring,
+    MinigameKind::Arrows,
+    MinigameKind::SlidingBar,
+    MinigameKind::DoubleRing,
+    MinigameKind::Rhythm,
+];
+
+/// Key presses made this frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Press {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+}
+
+/// How an attempt went
+#[derive(Debug, Clone, Copy)]
+pub struct Judgement {
+    /// How far off it was, in the same units as `window`
+    pub error: f32,
+    pub window: f32,
+    /// Seconds from the start to the press that decided it
+    pub time: f32,
+    /// How fast the minigame was running, in rounds per second
+    pub speed: f32,
+    /// Successes chained together in the attempt, for the combo
+    pub passes: u32,
+}
+
+impl Judgement {
+    pub fn hit(&self) -> bool {
+        self.error <= self.window
+    }
+}
+
+pub trait Minigame {
+    fn name(&self) -> &'static str;
+    /// Runs a frame, returning a judgement once the attempt is over
+    fn update(&mut self, presses: &[Press], dt: f32) -> Option<Judgement>;
+    fn draw(&self, ctx: &mut Context, assets: &mut Assets, coords: (u32, u32)) -> GameResult<()>;
+}
+
+/// Picks and sets up the minigame for a high five. A minion's own
+/// minigame or hand set ring wins over the difficulty's roll.
+pub fn start_minigame<R: Rng>(rng: &mut R,
+                              difficulty: &Difficulty,
+                              mutators: &Mutators,
+                              kind: Option<MinigameKind>,
+                              ring: Option<RingParams>) -> Box<dyn Minigame> {
+    let kind = match kind {
+        Some(kind) => kind,
+        None if ring.is_some() => MinigameKind::Ring,
+        None => {
+            if rng.gen::<f32>() < difficulty.variety {
+                MINIGAME_KINDS[rng.gen_range(1, MINIGAME_KINDS.len())]
+            }
+            else {
+                MinigameKind::Ring
+            }
+        }
+    };
+    // Double speed goes for every minigame, and for hand set rings too
+    let scale = mutators.speed_scale();
+    let speed = roll(rng, difficulty.ring_speed_min, difficulty.ring_speed_max) * scale;
+    let goal = roll(rng, difficulty.goal_min, difficulty.goal_max);
+    let window = difficulty.hit_window;
+    match kind {
+        MinigameKind::Ring => {
+            let ring = match ring {
+                Some(ring) => RingParams { speed: ring.speed * scale, ..ring },
+                None => RingParams { speed: speed, goal: goal },
+            };
+            // Later passes roll their own goal ring
+            let passes = rng.gen_range(1, difficulty.ring_passes.max(1) + 1);
+            let mut goals = vec![ring.goal];
+            for _ in 1..passes {
+                goals.push(roll(rng, difficulty.goal_min, difficulty.goal_max));
+            }
+            Box::new(RingGame::new(ring.speed, goals, window, mutators))
+        }
+        MinigameKind::Arrows => Box::new(ArrowGame::new(rng, speed, window)),
+        MinigameKind::SlidingBar => Box::new(BarGame::new(speed, goal, window)),
+        MinigameKind::DoubleRing => Box::new(DoubleRingGame::new(rng, speed, window, mutators)),
+        MinigameKind::Rhythm => Box::new(RhythmGame::new(rng, speed, window)),
+    }
+}
+
+fn screen_center(coords: (u32, u32)) -> Point {
+    Point::new((coords.0 / 2) as f32, (coords.1 / 2) as f32)
+}
+
+/// ********************************************************************
+/// Shrinking Ring
+///
+/// One high five can be a sequence of ring passes, each with its own
+/// goal ring. Landing a pass starts the ring over for the next one, and
+/// missing any of them misses the lot.
+/// ********************************************************************
+pub struct RingGame {
+    ring: Actor,
+    goal: Actor,
+    /// Goal ring scale for every pass, in order
+    goals: Vec<f32>,
+    pass: usize,
+    /// Worst error of the passes landed so far
+    worst: f32,
+    speed: f32,
+    window: f32,
+    time: f32,
+}
+
+impl RingGame {
+    pub fn new(speed: f32, goals: Vec<f32>, window: f32, mutators: &Mutators) -> RingGame {
+        let mut ring = create_ring();
+        ring.scale = Point::new(1.0, 1.0);
+        ring.velocity = Vector2::new(speed, speed);
+        let mut goal = create_goal_ring(mutators);
+        goal.scale = Point::new(goals[0], goals[0]);
+        RingGame {
+            ring: ring,
+            goal: goal,
+            goals: goals,
+            pass: 0,
+            worst: 0.0,
+            speed: speed,
+            window: window,
+            time: 0.0,
+        }
+    }
+
+    fn judge(&self, error: f32) -> Option<Judgement> {
+        Some(Judgement {
+            error: error,
+            window: self.window,
+            time: self.time,
+            speed: self.speed,
+            passes: self.pass as u32,
+        })
+    }
+}
+
+impl Minigame for RingGame {
+    fn name(&self) -> &'static str {
+        if self.goals.len() > 1 {
+            "Match Every Ring"
+        }
+        else {
+            "Match The Rings"
+        }
+    }
+
+    fn update(&mut self, presses: &[Press], dt: f32) -> Option<Judgement> {
+        shrink_ring(&mut self.ring, dt);
+        self.time += dt;
+        if presses.contains(&Press::Fire) {
+            let error = (self.ring.scale.x - self.goal.scale.x).abs();
+            if error > self.window {
+                return self.judge(error);
+            }
+            self.worst = self.worst.max(error);
+            self.pass += 1;
+            if self.pass == self.goals.len() {
+                let worst = self.worst;
+                return self.judge(worst);
+            }
+            let next = self.goals[self.pass];
+            self.goal.scale = Point::new(next, next);
+            self.ring.scale = Point::new(1.0, 1.0);
+            self.ring.velocity = Vector2::new(self.speed, self.speed);
+        }
+        None
+    }
+
+    fn draw(&self, ctx: &mut Context, assets: &mut Assets, coords: (u32, u32)) -> GameResult<()> {
+        draw_actor(assets, ctx, &self.ring, coords)?;
+        if self.goal.life > 0.0 {
+            draw_actor(assets, ctx, &self.goal, coords)?;
+        }
+        if self.goals.len() > 1 {
+            // A pip per pass, filled in once landed
+            let center = screen_center(coords);
+            let left = center.x - 30.0 * (self.goals.len() - 1) as f32 / 2.0;
+            for x in 0..self.goals.len() {
+                let mode = if x < self.pass { DrawMode::Fill } else { DrawMode::Line };
+                graphics::circle(ctx, mode, Point::new(left + 30.0 * x as f32, 100.0), 10.0, 16)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ********************************************************************
+/// Arrow Sequence
+/// ********************************************************************
+const ARROW_COUNT: usize = 4;
+const ARROW_SPACING: f32 = 100.0;
+
+pub struct ArrowGame {
+    sequence: Vec<Press>,
+    done: usize,
+    /// Seconds allowed for each arrow
+    per_arrow: f32,
+    arrow_time: f32,
+    /// Largest share of its time any arrow took so far
+    slowest: f32,
+    window: f32,
+    time: f32,
+    speed: f32,
+}
+
+impl ArrowGame {
+    pub fn new<R: Rng>(rng: &mut R, speed: f32, window: f32) -> ArrowGame {
+        let arrows = [Press::Up, Press::Down, Press::Left, Press::Right];
+        ArrowGame {
+            sequence: (0..ARROW_COUNT).map(|_| arrows[rng.gen_range(0, arrows.len())]).collect(),
+            done: 0,
+            per_arrow: 1.5 / speed,
+            arrow_time: 0.0,
+            slowest: 0.0,
+            window: window,
+            time: 0.0,
+            speed: speed,
+        }
+    }
+
+    fn judge(&self, error: f32) -> Option<Judgement> {
+        Some(Judgement {
+            error: error,
+            window: self.window,
+            time: self.time,
+            speed: self.speed,
+            passes: 1,
+        })
+    }
+}
+
+impl Minigame for ArrowGame {
+    fn name(&self) -> &'static str {
+        "Follow The Arrows"
+    }
+
+    fn update(&mut self, presses: &[Press], dt: f32) -> Option<Judgement> {
+        self.time += dt;
+        self.arrow_time += dt;
+        for press in presses.iter() {
+            if *press == Press::Fire {
+                continue;
+            }
+            if *press != self.sequence[self.done] {
+                return self.judge(self.window * 2.0);
+            }
+            self.slowest = self.slowest.max(self.arrow_time / self.per_arrow);
+            self.arrow_time = 0.0;
+            self.done += 1;
+            if self.done == self.sequence.len() {
+                // Slow arrows count as being further off
+                return self.judge(self.slowest * self.window);
+            }
+        }
+        if self.arrow_time > self.per_arrow {
+            return self.judge(self.window * 2.0);
+        }
+        None
+    }
+
+    fn draw(&self, ctx: &mut Context, _assets: &mut Assets, coords: (u32, u32)) -> GameResult<()> {
+        let center = screen_center(coords);
+        let left = center.x - ARROW_SPACING * (self.sequence.len() - 1) as f32 / 2.0;
+        for x in 0..self.sequence.len() {
+            let (dx, dy) = match self.sequence[x] {
+                Press::Up => (0.0, -1.0),
+                Press::Down => (0.0, 1.0),
+                Press::Left => (-1.0, 0.0),
+                _ => (1.0, 0.0),
+            };
+            let c = Point::new(left + ARROW_SPACING * x as f32, center.y);
+            let points = [Point::new(c.x + dx * 30.0, c.y + dy * 30.0),
+                          Point::new(c.x - dx * 20.0 - dy * 25.0, c.y - dy * 20.0 + dx * 25.0),
+                          Point::new(c.x - dx * 20.0 + dy * 25.0, c.y - dy * 20.0 - dx * 25.0)];
+            let color = if x < self.done {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            }
+            else if x == self.done {
+                Color::new(1.0, 0.85, 0.2, 1.0)
+            }
+            else {
+                graphics::WHITE
+            };
+            graphics::set_color(ctx, color)?;
+            graphics::polygon(ctx, DrawMode::Fill, &points)?;
+        }
+        // Time left on the current arrow
+        let left_share = (1.0 - self.arrow_time / self.per_arrow).max(0.0);
+        graphics::set_color(ctx, Color::new(1.0, 0.85, 0.2, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill,
+                            graphics::Rect::new(center.x, center.y + 80.0, 400.0 * left_share, 12.0))?;
+        graphics::set_color(ctx, graphics::WHITE)
+    }
+}
+
+/// ********************************************************************
+/// Sliding Bar
+/// ********************************************************************
+const BAR_WIDTH: f32 = 600.0;
+const BAR_HEIGHT: f32 = 30.0;
+/// How much slower the marker goes than a ring at the same speed
+const BAR_SPEED_SCALE: f32 = 0.6;
+
+pub struct BarGame {
+    /// Along the bar, from 0 to 1
+    pos: f32,
+    velocity: f32,
+    target: f32,
+    window: f32,
+    time: f32,
+}
+
+impl BarGame {
+    pub fn new(speed: f32, target: f32, window: f32) -> BarGame {
+        BarGame {
+            pos: 0.0,
+            velocity: speed * BAR_SPEED_SCALE,
+            target: target,
+            window: window,
+            time: 0.0,
+        }
+    }
+}
+
+impl Minigame for BarGame {
+    fn name(&self) -> &'static str {
+        "Stop The Bar"
+    }
+
+    fn update(&mut self, presses: &[Press], dt: f32) -> Option<Judgement> {
+        self.time += dt;
+        self.pos += self.velocity * dt;
+        if self.pos > 1.0 || self.pos < 0.0 {
+            self.pos = self.pos.max(0.0).min(1.0);
+            self.velocity *= -1.0;
+        }
+        if presses.contains(&Press::Fire) {
+            return Some(Judgement {
+                error: (self.pos - self.target).abs(),
+                window: self.window,
+                time: self.time,
+                speed: self.velocity.abs(),
+                passes: 1,
+            });
+        }
+        None
+    }
+
+    fn draw(&self, ctx: &mut Context, _assets: &mut Assets, coords: (u32, u32)) -> GameResult<()> {
+        let center = screen_center(coords);
+        let left = center.x - BAR_WIDTH / 2.0;
+        graphics::set_color(ctx, Color::new(0.2, 0.2, 0.2, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill,
+                            graphics::Rect::new(center.x, center.y, BAR_WIDTH, BAR_HEIGHT))?;
+        graphics::set_color(ctx, Color::new(0.2, 0.8, 0.2, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill,
+                            graphics::Rect::new(left + self.target * BAR_WIDTH, center.y,
+                                                self.window * 2.0 * BAR_WIDTH, BAR_HEIGHT))?;
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::rectangle(ctx, DrawMode::Fill,
+                            graphics::Rect::new(left + self.pos * BAR_WIDTH, center.y, 6.0, BAR_HEIGHT * 2.0))
+    }
+}
+
+/// ********************************************************************
+/// Double Ring
+/// ********************************************************************
+pub struct DoubleRingGame {
+    ring: Actor,
+    /// Outer goal first, then the inner one
+    goals: [Actor; 2],
+    beat: usize,
+    first_error: f32,
+    window: f32,
+    time: f32,
+}
+
+impl DoubleRingGame {
+    pub fn new<R: Rng>(rng: &mut R, speed: f32, window: f32, mutators: &Mutators) -> DoubleRingGame {
+        let mut ring = create_ring();
+        ring.scale = Point::new(1.0, 1.0);
+        ring.velocity = Vector2::new(speed, speed);
+        let inner = rng.gen_range(0.15, 0.45);
+        let outer = inner + rng.gen_range(0.25, 0.45);
+        let mut goals = [create_goal_ring(mutators), create_goal_ring(mutators)];
+        goals[0].scale = Point::new(outer, outer);
+        goals[1].scale = Point::new(inner, inner);
+        DoubleRingGame {
+            ring: ring,
+            goals: goals,
+            beat: 0,
+            first_error: 0.0,
+            window: window,
+            time: 0.0,
+        }
+    }
+}
+
+impl Minigame for DoubleRingGame {
+    fn name(&self) -> &'static str {
+        "Two Beat High Five"
+    }
+
+    fn update(&mut self, presses: &[Press], dt: f32) -> Option<Judgement> {
+        shrink_ring(&mut self.ring, dt);
+        self.time += dt;
+        if presses.contains(&Press::Fire) {
+            let error = (self.ring.scale.x - self.goals[self.beat].scale.x).abs();
+            if self.beat == 0 && error <= self.window {
+                self.first_error = error;
+                self.beat = 1;
+                return None;
+            }
+            return Some(Judgement {
+                error: error.max(self.first_error),
+                window: self.window,
+                time: self.time,
+                speed: self.ring.velocity.x.abs(),
+                passes: 1,
+            });
+        }
+        None
+    }
+
+    fn draw(&self, ctx: &mut Context, assets: &mut Assets, coords: (u32, u32)) -> GameResult<()> {
+        draw_actor(assets, ctx, &self.ring, coords)?;
+        for goal in self.goals[self.beat..].iter().filter(|goal| goal.life > 0.0) {
+            draw_actor(assets, ctx, goal, coords)?;
+        }
+        Ok(())
+    }
+}
+
+/// ********************************************************************
+/// Rhythm
+/// ********************************************************************
+/// Beat patterns, in beats between each press
+const RHYTHM_PATTERNS: [[f32; 4]; 4] = [
+    [1.0, 1.0, 1.0, 1.0],
+    [1.0, 0.5, 0.5, 1.0],
+    [0.5, 0.5, 1.0, 1.0],
+    [1.0, 1.0, 0.5, 0.5],
+];
+/// Seconds before the first beat, so it can be seen coming
+const RHYTHM_LEAD_IN: f32 = 1.5;
+/// How fast the beats scroll towards the line, in pixels per second
+const RHYTHM_SCROLL: f32 = 300.0;
+/// Seconds of leeway per unit of hit window
+const RHYTHM_WINDOW_SCALE: f32 = 2.0;
+
+pub struct RhythmGame {
+    /// Seconds from the start each beat lands on
+    beats: Vec<f32>,
+    next: usize,
+    worst: f32,
+    window: f32,
+    time: f32,
+    speed: f32,
+}
+
+impl RhythmGame {
+    pub fn new<R: Rng>(rng: &mut R, speed: f32, window: f32) -> RhythmGame {
+        let pattern = RHYTHM_PATTERNS[rng.gen_range(0, RHYTHM_PATTERNS.len())];
+        let interval = 1.0 / speed;
+        let mut at = RHYTHM_LEAD_IN;
+        let mut beats = vec![];
+        for gap in pattern.iter() {
+            beats.push(at);
+            at += gap * interval;
+        }
+        RhythmGame {
+            beats: beats,
+            next: 0,
+            worst: 0.0,
+            window: window,
+            time: 0.0,
+            speed: speed,
+        }
+    }
+
+    fn judge(&self, error: f32) -> Option<Judgement> {
+        Some(Judgement {
+            error: error,
+            window: self.window,
+            time: self.time,
+            speed: self.speed,
+            passes: 1,
+        })
+    }
+}
+
+impl Minigame for RhythmGame {
+    fn name(&self) -> &'static str {
+        "Keep The Beat"
+    }
+
+    fn update(&mut self, presses: &[Press], dt: f32) -> Option<Judgement> {
+        self.time += dt;
+        let leeway = self.window * RHYTHM_WINDOW_SCALE;
+        if presses.contains(&Press::Fire) {
+            let off = (self.time - self.beats[self.next]).abs();
+            if off > leeway {
+                return self.judge(off / RHYTHM_WINDOW_SCALE);
+            }
+            self.worst = self.worst.max(off / RHYTHM_WINDOW_SCALE);
+            self.next += 1;
+            if self.next == self.beats.len() {
+                return self.judge(self.worst);
+            }
+        }
+        else if self.time > self.beats[self.next] + leeway {
+            // Let a beat go by
+            return self.judge(self.window * 2.0);
+        }
+        None
+    }
+
+    fn draw(&self, ctx: &mut Context, _assets: &mut Assets, coords: (u32, u32)) -> GameResult<()> {
+        let center = screen_center(coords);
+        let line_x = center.x - 250.0;
+        graphics::set_color(ctx, Color::new(0.2, 0.2, 0.2, 1.0))?;
+        graphics::line(ctx, &[Point::new(line_x - 50.0, center.y), Point::new(center.x + 450.0, center.y)])?;
+        graphics::set_color(ctx, Color::new(1.0, 0.85, 0.2, 1.0))?;
+        graphics::line(ctx, &[Point::new(line_x, center.y - 50.0), Point::new(line_x, center.y + 50.0)])?;
+        graphics::set_color(ctx, graphics::WHITE)?;
+        for beat in self.beats[self.next..].iter() {
+            let x = line_x + (beat - self.time) * RHYTHM_SCROLL;
+            graphics::circle(ctx, DrawMode::Fill, Point::new(x, center.y), 20.0, 16)?;
+        }
+        Ok(())
+    }
+}
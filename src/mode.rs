@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use ggez::{Context, GameResult, GameError};
+use serde_json;
+
+/// ********************************************************************
+/// Game Modes
+///
+/// Picked on the title screen. Each mode only changes a few rules in the
+/// state machine, asked about through the methods below:
+///
+/// * Classic: the level's countdown, with time back for every friend,
+///   played in rounds with the shop in between
+/// * Endless: no clock, the run ends after a few dead minions
+/// * Time Attack: a flat minute on the clock and no time back
+/// * Zen: no clock and no dead minions, a missed high five just walks off
+/// * Sudden Death: Classic rules, but the first missed high five ends it
+/// * Daily Challenge: Classic rules, with the day's minions and rings and
+///   none of the mutators
+/// ********************************************************************
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    Classic,
+    Endless,
+    TimeAttack,
+    Zen,
+    SuddenDeath,
+    DailyChallenge,
+}
+
+pub const GAME_MODES: [GameMode; 6] = [
+    GameMode::Classic,
+    GameMode::Endless,
+    GameMode::TimeAttack,
+    GameMode::Zen,
+    GameMode::SuddenDeath,
+    GameMode::DailyChallenge,
+];
+
+/// Dead minions before an Endless run is over
+pub const ENDLESS_KILLS: u32 = 5;
+const TIME_ATTACK_TIME: f32 = 60.0;
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Classic
+    }
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameMode::Classic => "Classic",
+            GameMode::Endless => "Endless",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Zen => "Zen",
+            GameMode::SuddenDeath => "Sudden Death",
+            GameMode::DailyChallenge => "Daily Challenge",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            GameMode::Classic => "Beat the clock, every friend buys more time",
+            GameMode::Endless => "No clock, but five dead minions and you're done",
+            GameMode::TimeAttack => "One minute flat, no time back",
+            GameMode::Zen => "No clock, nobody gets hurt",
+            GameMode::SuddenDeath => "One missed high five ends the run",
+            GameMode::DailyChallenge => "Everyone gets the same minions and rings today, no mutators",
+        }
+    }
+
+    /// Seconds on the clock at the start
+    pub fn start_time(&self, level_timer: f32) -> f32 {
+        match *self {
+            GameMode::TimeAttack => TIME_ATTACK_TIME,
+            _ => level_timer,
+        }
+    }
+
+    /// Whether the clock runs at all
+    pub fn timed(&self) -> bool {
+        match *self {
+            GameMode::Endless | GameMode::Zen => false,
+            _ => true,
+        }
+    }
+
+    /// Whether friends add time back onto the clock
+    pub fn time_bonus(&self) -> bool {
+        match *self {
+            GameMode::Classic | GameMode::SuddenDeath | GameMode::DailyChallenge => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the run is split into rounds with a friend quota, see
+    /// `rounds`. Classic only: the shop would give Time Attack its time
+    /// back and make the daily challenge depend on what was bought.
+    pub fn rounds(&self) -> bool {
+        *self == GameMode::Classic
+    }
+
+    /// Whether a missed high five kills the minion
+    pub fn deaths(&self) -> bool {
+        *self != GameMode::Zen
+    }
+
+    /// Whether spawns and rings come from the day's seed
+    pub fn daily(&self) -> bool {
+        *self == GameMode::DailyChallenge
+    }
+
+    /// Whether difficulty adapts to the player. Off for the daily
+    /// challenge so everyone gets the same rings.
+    pub fn adaptive(&self) -> bool {
+        !self.daily()
+    }
+
+    /// Whether the run is over after `kills` dead minions
+    pub fn out_of_lives(&self, kills: u32) -> bool {
+        match *self {
+            GameMode::Endless => kills >= ENDLESS_KILLS,
+            GameMode::SuddenDeath => kills >= 1,
+            _ => false,
+        }
+    }
+}
+
+/// ********************************************************************
+/// High Scores
+///
+/// The best few scores of each mode, kept in the profile. They used to
+/// have a file of their own, which is still read once to bring them into
+/// the first profile.
+/// ********************************************************************
+
+pub const HIGH_SCORE_FILE: &'static str = "/high_scores.json";
+const HIGH_SCORES_KEPT: usize = 5;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    /// Best first, keyed by mode name
+    pub tables: BTreeMap<String, Vec<u32>>,
+}
+
+impl HighScores {
+    pub fn table(&self, mode: GameMode) -> Vec<u32> {
+        self.tables.get(mode.name()).cloned().unwrap_or_default()
+    }
+
+    /// Adds a score to its mode's table, returning its place if it made it
+    /// onto the table
+    pub fn record(&mut self, mode: GameMode, score: u32) -> Option<usize> {
+        let table = self.tables.entry(mode.name().to_string()).or_insert_with(Vec::new);
+        let place = table.iter().position(|s| score > *s).unwrap_or(table.len());
+        if place >= HIGH_SCORES_KEPT {
+            return None;
+        }
+        table.insert(place, score);
+        table.truncate(HIGH_SCORES_KEPT);
+        Some(place)
+    }
+}
+
+fn read_high_scores(ctx: &mut Context) -> GameResult<HighScores> {
+    let mut file = ctx.filesystem.open(HIGH_SCORE_FILE)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    serde_json::from_str(&contents)
+        .map_err(|e| GameError::ResourceLoadError(format!("Bad high score file: {}", e)))
+}
+
+/// Reads the old high score file, starting from empty tables if there is none
+pub fn load_high_scores(ctx: &mut Context) -> HighScores {
+    if !ctx.filesystem.is_file(HIGH_SCORE_FILE) {
+        return HighScores::default();
+    }
+    match read_high_scores(ctx) {
+        Ok(scores) => scores,
+        Err(e) => {
+            println!("Could not read high scores, starting afresh");
+            println!("Error: {}", e);
+            HighScores::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_classic_is_played_in_rounds() {
+        for mode in GAME_MODES.iter() {
+            assert_eq!(mode.rounds(), *mode == GameMode::Classic, "{}", mode.name());
+        }
+    }
+
+    #[test]
+    fn time_attack_never_gets_time_back() {
+        let with_bonus: Vec<GameMode> = GAME_MODES.iter().cloned().filter(|m| m.time_bonus()).collect();
+        assert_eq!(with_bonus, vec![GameMode::Classic, GameMode::SuddenDeath, GameMode::DailyChallenge]);
+    }
+
+    #[test]
+    fn only_timed_modes_get_time_back() {
+        for mode in GAME_MODES.iter() {
+            assert!(!mode.time_bonus() || mode.timed(), "{}", mode.name());
+        }
+    }
+}
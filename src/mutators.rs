@@ -0,0 +1,86 @@
+/// ********************************************************************
+/// Mutators
+///
+/// Optional twists toggled on the title screen with the number keys.
+/// Each one makes the run harder and adds its bonus to the score
+/// multiplier; the bonuses add up, so all six together are worth 2.65x.
+/// ********************************************************************
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Mutators {
+    /// Left is right and right is left
+    pub mirrored: bool,
+    /// The goal ring isn't drawn
+    pub invisible_goal: bool,
+    /// Every minigame runs twice as fast
+    pub double_speed: bool,
+    pub giant_minions: bool,
+    /// Fire pits jump somewhere else every `FIRE_MOVE_TIME` seconds
+    pub moving_fire: bool,
+    /// No reminder that there's a body lying around
+    pub no_body_reminder: bool,
+}
+
+pub const MUTATOR_COUNT: usize = 6;
+pub const FIRE_MOVE_TIME: f32 = 10.0;
+/// Giant minions are this much bigger than usual
+pub const GIANT_MINION_SCALE: f32 = 1.6;
+
+impl Mutators {
+    fn flag(&mut self, index: usize) -> Option<&mut bool> {
+        match index {
+            0 => Some(&mut self.mirrored),
+            1 => Some(&mut self.invisible_goal),
+            2 => Some(&mut self.double_speed),
+            3 => Some(&mut self.giant_minions),
+            4 => Some(&mut self.moving_fire),
+            5 => Some(&mut self.no_body_reminder),
+            _ => None,
+        }
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(flag) = self.flag(index) {
+            *flag = !*flag;
+        }
+    }
+
+    pub fn is_on(&self, index: usize) -> bool {
+        let mut copy = *self;
+        copy.flag(index).map_or(false, |flag| *flag)
+    }
+
+    pub fn name(index: usize) -> &'static str {
+        match index {
+            0 => "Mirrored Controls",
+            1 => "Invisible Goal Ring",
+            2 => "Double Speed",
+            3 => "Giant Minions",
+            4 => "Wandering Fire",
+            5 => "No Body Reminder",
+            _ => "",
+        }
+    }
+
+    /// Multiplies the speed every minigame is started at
+    pub fn speed_scale(&self) -> f32 {
+        if self.double_speed { 2.0 } else { 1.0 }
+    }
+
+    /// What each mutator adds to the multiplier
+    pub fn bonus(index: usize) -> f32 {
+        match index {
+            0 => 0.25,
+            1 => 0.5,
+            2 => 0.5,
+            3 => 0.1,
+            4 => 0.2,
+            5 => 0.1,
+            _ => 0.0,
+        }
+    }
+
+    pub fn score_multiplier(&self) -> f32 {
+        1.0 + (0..MUTATOR_COUNT).filter(|x| self.is_on(*x)).map(Mutators::bonus).sum::<f32>()
+    }
+}
@@ -0,0 +1,136 @@
+use ggez::{Context, GameResult};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point};
+
+use minigame::Press;
+use settings::open_settings;
+use suspend::{in_run, capture, save_suspended};
+use tutorial::end_tutorial;
+use {MainState, draw_text, start_run};
+
+/// ********************************************************************
+/// Pause Menu
+///
+/// State 14, drawn over the run it paused. Nothing in the run is
+/// updated while it's up, so the clock and the high five ring both
+/// stop where they were.
+///
+/// ggez quits on Escape before the game ever sees the key, so a quit
+/// mid-run opens this menu instead, and a second one while it's up
+/// suspends the run and quits for real. Start on a controller and the
+/// window losing focus open it too.
+/// ********************************************************************
+
+const PAUSE_ENTRIES: [&str; 4] = ["Resume", "Restart", "Settings", "Quit To Title"];
+
+#[derive(Debug, Default)]
+pub struct PauseMenu {
+    pub choice: usize,
+    /// State to go back to
+    pub return_state: u32,
+}
+
+/// The state of the run under the pause menu, or under settings opened
+/// from it. Any other state is its own.
+pub fn run_state(game: &MainState) -> u32 {
+    match game.state {
+        14 => game.pause_menu.return_state,
+        13 if game.settings_menu.return_state == 14 => game.pause_menu.return_state,
+        state => state,
+    }
+}
+
+/// Pauses the run, if there's one going
+pub fn open_pause(game: &mut MainState) {
+    if !in_run(game.state) {
+        return;
+    }
+    game.pause_menu = PauseMenu {
+        choice: 0,
+        return_state: game.state,
+    };
+    game.state = 14;
+}
+
+pub fn resume(game: &mut MainState) {
+    game.state = game.pause_menu.return_state;
+}
+
+/// Suspends the run, so it's offered as "Continue" on the title screen.
+/// There's nothing to keep of the tutorial.
+fn quit_to_title(game: &mut MainState, ctx: &mut Context) {
+    resume(game);
+    if game.tutorial.is_some() {
+        end_tutorial(game);
+    }
+    else {
+        let run = capture(game);
+        if let Err(e) = save_suspended(ctx, game.profile_id, &run) {
+            println!("Could not suspend the run");
+            println!("Error: {}", e);
+        }
+        game.suspended = Some(run);
+        game.mode_choice = 0;
+    }
+    game.state = 4;
+}
+
+pub fn pause_update(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let entries = PAUSE_ENTRIES.len();
+    let presses = game.input.presses.clone();
+    for press in presses.iter() {
+        match *press {
+            Press::Up => {
+                game.pause_menu.choice = (game.pause_menu.choice + entries - 1) % entries;
+            }
+            Press::Down => {
+                game.pause_menu.choice = (game.pause_menu.choice + 1) % entries;
+            }
+            Press::Fire => {
+                match game.pause_menu.choice {
+                    0 => resume(game),
+                    1 => {
+                        let index = game.level_index;
+                        start_run(game, ctx, index)?;
+                        game.state = 0;
+                    }
+                    2 => open_settings(game),
+                    _ => quit_to_title(game, ctx),
+                }
+                break;
+            }
+            _ => (),
+        }
+    }
+    game.input.any_key = false;
+    game.input.fire = false;
+    game.input.pickup = false;
+    game.input.drop = false;
+    Ok(())
+}
+
+pub fn draw_pause(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let center = Point::new((game.screen_width / 2) as f32, (game.screen_height / 2) as f32);
+    graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 0.5))?;
+    graphics::rectangle(ctx, DrawMode::Fill,
+                        graphics::Rect::new(center.x, center.y,
+                                            game.screen_width as f32, game.screen_height as f32))?;
+    graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.9))?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(center.x, center.y, 360.0, 260.0))?;
+    graphics::set_color(ctx, graphics::BLACK)?;
+    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+    let mut title_text = graphics::Text::new(ctx, "Paused", font)?;
+    draw_text(ctx, &mut title_text, Point::new(center.x, center.y - 100.0))?;
+    for x in 0..PAUSE_ENTRIES.len() {
+        let marker = if x == game.pause_menu.choice { "> " } else { "" };
+        let line = format!("{}{}", marker, PAUSE_ENTRIES[x]);
+        let mut line_text = graphics::Text::new(ctx, &line, font)?;
+        let line_dest = Point::new(center.x - 150.0 + (line_text.width() / 2) as f32,
+                                   center.y - 55.0 + 30.0 * x as f32);
+        draw_text(ctx, &mut line_text, line_dest)?;
+    }
+    let mut help_text = graphics::Text::new(ctx, "Esc Again Saves And Quits", font)?;
+    draw_text(ctx, &mut help_text, Point::new(center.x, center.y + 100.0))?;
+    graphics::set_color(ctx, graphics::WHITE)?;
+    Ok(())
+}
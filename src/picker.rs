@@ -0,0 +1,243 @@
+use ggez::{Context, GameResult};
+use ggez::event::{Keycode, Mod};
+use sdl2::keyboard::{LSHIFTMOD, RSHIFTMOD, CAPSMOD};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point};
+
+use profile::{MAX_PROFILE_NAME, create_profile, save_profile_index, delete_profile};
+use settings::open_settings;
+use suspend::delete_suspended;
+use {MainState, draw_text, select_profile};
+
+/// ********************************************************************
+/// Profile Picker
+///
+/// State 12, between loading and the title screen. The profiles are
+/// listed with "New Profile" after them:
+///
+/// * Up/Down and Enter pick one
+/// * R renames the highlighted profile, typing over its name
+/// * Delete, twice, deletes it, as long as it isn't the only one
+/// * Tab opens its settings
+///
+/// Keys only ask for things to happen; anything touching files is done
+/// in `picker_update`, which gets the context.
+/// ********************************************************************
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickerRequest {
+    Pick(u32),
+    Create,
+    Rename(u32, String),
+    Delete(u32),
+    Settings(u32),
+}
+
+#[derive(Debug, Default)]
+pub struct PickerState {
+    pub choice: usize,
+    /// The name being typed, while renaming
+    pub renaming: Option<String>,
+    /// Delete has been pressed once on the highlighted profile
+    pub confirm_delete: bool,
+    pub request: Option<PickerRequest>,
+    pub message: String,
+}
+
+/// The letter, digit or space a key types, if any
+fn typed_char(keycode: Keycode, keymod: Mod) -> Option<char> {
+    if keycode == Keycode::Space {
+        return Some(' ');
+    }
+    let name = keycode.name();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => {
+            if keymod.intersects(LSHIFTMOD | RSHIFTMOD | CAPSMOD) {
+                Some(c.to_ascii_uppercase())
+            }
+            else {
+                Some(c.to_ascii_lowercase())
+            }
+        }
+        _ => None,
+    }
+}
+
+fn rename_key(game: &mut MainState, keycode: Keycode, keymod: Mod) {
+    let id = game.profiles.profiles[game.picker.choice].id;
+    let mut name = game.picker.renaming.take().unwrap_or_default();
+    match keycode {
+        Keycode::Return => {
+            let name = name.trim().to_string();
+            if name.len() > 0 {
+                game.picker.request = Some(PickerRequest::Rename(id, name));
+                return;
+            }
+            game.picker.message = "A profile needs a name".to_string();
+        }
+        Keycode::Backspace => {
+            name.pop();
+        }
+        _ => {
+            if let Some(c) = typed_char(keycode, keymod) {
+                if name.len() < MAX_PROFILE_NAME {
+                    name.push(c);
+                }
+            }
+        }
+    }
+    game.picker.renaming = Some(name);
+}
+
+pub fn picker_key(game: &mut MainState, keycode: Keycode, keymod: Mod) {
+    if game.picker.renaming.is_some() {
+        rename_key(game, keycode, keymod);
+        return;
+    }
+    let count = game.profiles.profiles.len();
+    let len = count + 1;
+    let highlighted = if game.picker.choice < count {
+        Some(game.profiles.profiles[game.picker.choice].id)
+    }
+    else {
+        None
+    };
+    let confirm_delete = game.picker.confirm_delete;
+    game.picker.confirm_delete = false;
+    game.picker.message = String::new();
+    match keycode {
+        Keycode::Up | Keycode::W => {
+            game.picker.choice = (game.picker.choice + len - 1) % len;
+        }
+        Keycode::Down | Keycode::S => {
+            game.picker.choice = (game.picker.choice + 1) % len;
+        }
+        Keycode::Return | Keycode::Space => {
+            game.picker.request = Some(match highlighted {
+                Some(id) => PickerRequest::Pick(id),
+                None => PickerRequest::Create,
+            });
+        }
+        Keycode::R => {
+            if let Some(id) = highlighted {
+                game.picker.renaming = Some(game.profiles.name(id).to_string());
+            }
+        }
+        Keycode::Delete => {
+            if let Some(id) = highlighted {
+                if count == 1 {
+                    game.picker.message = "The last profile can't be deleted".to_string();
+                }
+                else if confirm_delete {
+                    game.picker.request = Some(PickerRequest::Delete(id));
+                }
+                else {
+                    game.picker.confirm_delete = true;
+                    game.picker.message = format!("Press Delete again to delete {}", game.profiles.name(id));
+                }
+            }
+        }
+        Keycode::Tab => {
+            if let Some(id) = highlighted {
+                game.picker.request = Some(PickerRequest::Settings(id));
+            }
+        }
+        _ => (),
+    }
+}
+
+fn save_index(game: &mut MainState, ctx: &mut Context) {
+    if let Err(e) = save_profile_index(ctx, &game.profiles) {
+        println!("Could not save the profile index");
+        println!("Error: {}", e);
+    }
+}
+
+/// Carries out whatever the keys asked for
+pub fn picker_update(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let request = match game.picker.request.take() {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    match request {
+        PickerRequest::Pick(id) => {
+            select_profile(game, ctx, id)?;
+            game.state = 4;
+        }
+        PickerRequest::Create => {
+            let name = game.profiles.fresh_name();
+            if let Err(e) = create_profile(ctx, &mut game.profiles, &name) {
+                game.picker.message = format!("Could not make the profile: {}", e);
+            }
+            save_index(game, ctx);
+            game.picker.choice = game.profiles.profiles.len() - 1;
+            game.picker.renaming = Some(name);
+        }
+        PickerRequest::Rename(id, name) => {
+            game.profiles.rename(id, &name);
+            save_index(game, ctx);
+        }
+        PickerRequest::Delete(id) => {
+            if let Err(e) = delete_profile(ctx, id).and_then(|_| delete_suspended(ctx, id)) {
+                game.picker.message = format!("Could not delete the profile: {}", e);
+                return Ok(());
+            }
+            game.profiles.remove(id);
+            save_index(game, ctx);
+            game.picker.choice = game.picker.choice.min(game.profiles.profiles.len() - 1);
+            // Whatever was loaded is gone, so load one that's still there
+            if game.profile_id == id {
+                let first = game.profiles.profiles[0].id;
+                game.profile_dirty = false;
+                select_profile(game, ctx, first)?;
+            }
+        }
+        PickerRequest::Settings(id) => {
+            select_profile(game, ctx, id)?;
+            open_settings(game);
+        }
+    }
+    Ok(())
+}
+
+pub fn draw_picker(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let center = Point::new((game.screen_width / 2) as f32, (game.screen_height / 2) as f32);
+    graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.9))?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(center.x, center.y, 560.0, 440.0))?;
+    graphics::set_color(ctx, graphics::BLACK)?;
+    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+    let mut title_text = graphics::Text::new(ctx, "Who's Playing?", font)?;
+    draw_text(ctx, &mut title_text, Point::new(center.x, center.y - 190.0))?;
+    let mut lines: Vec<String> = game.profiles.profiles.iter().map(|entry| entry.name.clone()).collect();
+    lines.push("New Profile".to_string());
+    for x in 0..lines.len() {
+        let line = if x == game.picker.choice {
+            match game.picker.renaming {
+                Some(ref name) => format!("> {}_", name),
+                None => format!("> {}", lines[x]),
+            }
+        }
+        else {
+            lines[x].clone()
+        };
+        let mut line_text = graphics::Text::new(ctx, &line, font)?;
+        let line_dest = Point::new(center.x - 250.0 + (line_text.width() / 2) as f32,
+                                   center.y - 150.0 + 30.0 * x as f32);
+        draw_text(ctx, &mut line_text, line_dest)?;
+    }
+    let help = if game.picker.renaming.is_some() {
+        "Type A Name, Enter When Done"
+    }
+    else {
+        "Enter Picks, R Renames, Delete Deletes, Tab For Settings"
+    };
+    let mut help_text = graphics::Text::new(ctx, help, font)?;
+    draw_text(ctx, &mut help_text, Point::new(center.x, center.y + 195.0))?;
+    if game.picker.message.len() > 0 {
+        let mut message_text = graphics::Text::new(ctx, &game.picker.message, font)?;
+        draw_text(ctx, &mut message_text, Point::new(center.x, center.y + 165.0))?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)?;
+    Ok(())
+}
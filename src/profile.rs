@@ -0,0 +1,620 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use ggez::{Context, GameResult, GameError};
+use ggez::graphics::Color;
+use serde_json;
+use serde_json::Value;
+
+use achievements::Achievement;
+use archetype::{Archetype, ARCHETYPES};
+use daily::{DailyResults, load_daily_results};
+use level::LEVELS;
+use mode::{HighScores, load_high_scores};
+use settings::{Settings, KeyBindings};
+
+/// ********************************************************************
+/// Profile
+///
+/// What carries over from one run to the next: every friend ever made,
+/// and what those friends have unlocked. Lord skins, minion types and
+/// levels each unlock once enough friends have been made in total, so
+/// the profile only needs the count; nothing is lost if the milestones
+/// are moved around later.
+///
+/// A few people can share a machine, each with a profile of their own:
+/// settings, key bindings, high scores and daily results along with the
+/// above. Every profile is a file in `PROFILE_DIR`, named by its id, and
+/// the index there has their names.
+///
+/// The file has a version. So far every version has only added fields,
+/// which older files pick up at their defaults, so reading an old file
+/// just stamps the current version on it. A copy of the old file is set
+/// aside first all the same, so a bad read never eats a save.
+/// ********************************************************************
+
+pub const PROFILE_DIR: &'static str = "/profiles";
+pub const PROFILE_INDEX_FILE: &'static str = "/profiles/index.json";
+/// Where an index that couldn't be read is set aside
+const BAD_INDEX_FILE: &'static str = "/profiles/index_bad.json";
+/// Where the one profile lived before there could be several
+const LEGACY_PROFILE_FILE: &'static str = "/profile.json";
+/// Bump this whenever the format changes. Version 1 was the first one
+/// with the field, version 2 took in settings, key bindings, high scores
+/// and daily results.
+pub const PROFILE_VERSION: u32 = 2;
+pub const MAX_PROFILE_NAME: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Skin {
+    Classic,
+    Golden,
+    Shadow,
+    Royal,
+}
+
+pub const SKINS: [Skin; 4] = [
+    Skin::Classic,
+    Skin::Golden,
+    Skin::Shadow,
+    Skin::Royal,
+];
+
+impl Default for Skin {
+    fn default() -> Self {
+        Skin::Classic
+    }
+}
+
+pub struct SkinInfo {
+    pub name: &'static str,
+    /// Skins are the classic lord's walking frames in this tint
+    pub tint: Color,
+    /// Friends needed in total to unlock it
+    pub unlock_friends: u32,
+}
+
+impl Skin {
+    pub fn info(&self) -> SkinInfo {
+        match *self {
+            Skin::Classic => SkinInfo {
+                name: "Classic Lord",
+                tint: Color::new(1.0, 1.0, 1.0, 1.0),
+                unlock_friends: 0,
+            },
+            Skin::Golden => SkinInfo {
+                name: "Golden Lord",
+                tint: Color::new(1.0, 0.85, 0.3, 1.0),
+                unlock_friends: 50,
+            },
+            Skin::Shadow => SkinInfo {
+                name: "Shadow Lord",
+                tint: Color::new(0.45, 0.45, 0.55, 1.0),
+                unlock_friends: 150,
+            },
+            Skin::Royal => SkinInfo {
+                name: "Royal Lord",
+                tint: Color::new(0.75, 0.5, 1.0, 1.0),
+                unlock_friends: 300,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unlock {
+    Skin(Skin),
+    Minion(Archetype),
+    /// Index into `LEVELS`
+    Level(usize),
+}
+
+impl Unlock {
+    pub fn name(&self) -> String {
+        match *self {
+            Unlock::Skin(skin) => skin.info().name.to_string(),
+            Unlock::Minion(archetype) => archetype.info().name.to_string(),
+            Unlock::Level(level) => format!("Level: {}", LEVELS[level].name),
+        }
+    }
+
+    pub fn friends(&self) -> u32 {
+        match *self {
+            Unlock::Skin(skin) => skin.info().unlock_friends,
+            Unlock::Minion(archetype) => archetype.info().unlock_friends,
+            Unlock::Level(level) => LEVELS[level].unlock_friends,
+        }
+    }
+}
+
+/// Everything that has to be unlocked, soonest first
+pub fn unlocks() -> Vec<Unlock> {
+    let mut all = vec![];
+    all.extend(SKINS.iter().map(|skin| Unlock::Skin(*skin)));
+    all.extend(ARCHETYPES.iter().map(|archetype| Unlock::Minion(*archetype)));
+    all.extend((0..LEVELS.len()).map(Unlock::Level));
+    all.retain(|unlock| unlock.friends() > 0);
+    all.sort_by_key(|unlock| unlock.friends());
+    all
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// Files from before there was a version read as 0
+    #[serde(default)]
+    pub version: u32,
+    /// Friends made over every run
+    #[serde(default)]
+    pub total_friends: u32,
+    #[serde(default)]
+    pub runs: u32,
+    #[serde(default)]
+    pub best_score: u32,
+    /// Picked on the title screen, kept for next time
+    #[serde(default)]
+    pub skin: Skin,
+    #[serde(default)]
+    pub level: usize,
+    /// Every achievement ever earned
+    #[serde(default)]
+    pub achievements: BTreeSet<Achievement>,
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub bindings: KeyBindings,
+    #[serde(default)]
+    pub high_scores: HighScores,
+    #[serde(default)]
+    pub daily_results: DailyResults,
+    /// Played through or skipped, so it isn't shown again
+    #[serde(default)]
+    pub tutorial_done: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            version: PROFILE_VERSION,
+            total_friends: 0,
+            runs: 0,
+            best_score: 0,
+            skin: Skin::Classic,
+            level: 0,
+            achievements: BTreeSet::new(),
+            settings: Settings::default(),
+            bindings: KeyBindings::default(),
+            high_scores: HighScores::default(),
+            daily_results: DailyResults::default(),
+            tutorial_done: false,
+        }
+    }
+}
+
+impl Profile {
+    pub fn unlocked(&self, unlock: Unlock) -> bool {
+        self.total_friends >= unlock.friends()
+    }
+
+    pub fn next_unlock(&self) -> Option<Unlock> {
+        unlocks().into_iter().find(|unlock| !self.unlocked(*unlock))
+    }
+
+    /// Drops picks from the last run that aren't unlocked any more, or
+    /// point at a level that isn't there
+    fn sanitize(&mut self) {
+        if self.level >= LEVELS.len() || !self.unlocked(Unlock::Level(self.level)) {
+            self.level = 0;
+        }
+        if !self.unlocked(Unlock::Skin(self.skin)) {
+            self.skin = Skin::Classic;
+        }
+    }
+
+    /// Adds a finished run, returning anything it unlocked
+    pub fn record_run(&mut self, friends: u32, score: u32) -> Vec<Unlock> {
+        let before = self.total_friends;
+        self.total_friends += friends;
+        self.runs += 1;
+        self.best_score = self.best_score.max(score);
+        let after = self.total_friends;
+        unlocks().into_iter()
+            .filter(|unlock| unlock.friends() > before && unlock.friends() <= after)
+            .collect()
+    }
+}
+
+/// Where the profile with this id is kept
+pub fn profile_path(id: u32) -> String {
+    format!("{}/{}.json", PROFILE_DIR, id)
+}
+
+/// Copies a profile that's about to be brought up to date or given up
+/// on, so it can be got back by hand
+fn back_up_profile(ctx: &mut Context, path: &str, contents: &[u8], version: u32) {
+    let backup = format!("{}_v{}.json", path.trim_end_matches(".json"), version);
+    let result = ctx.filesystem.create(&backup)
+        .and_then(|mut file| file.write_all(contents).map_err(GameError::from));
+    if let Err(e) = result {
+        println!("Could not back up the profile to {}", backup);
+        println!("Error: {}", e);
+    }
+}
+
+/// Reads a profile from a file's contents, bringing it up to date if
+/// it's old. Also gives the version the file was at, 0 if it can't be told.
+fn read_profile(contents: &[u8], path: &str) -> (u32, GameResult<Profile>) {
+    let bad = |e: serde_json::Error| GameError::ResourceLoadError(format!("Bad profile file {}: {}", path, e));
+    let value: Value = match serde_json::from_slice(contents) {
+        Ok(value) => value,
+        Err(e) => return (0, Err(bad(e))),
+    };
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > PROFILE_VERSION {
+        let e = GameError::ResourceLoadError(format!("Profile {} is from a newer version ({})", path, version));
+        return (version, Err(e));
+    }
+    if version < PROFILE_VERSION {
+        println!("Updating profile {} from version {} to {}", path, version, PROFILE_VERSION);
+    }
+    let profile = serde_json::from_value(value).map(|mut profile: Profile| {
+        // Nothing to change between versions yet, the missing fields
+        // have taken their defaults
+        profile.version = PROFILE_VERSION;
+        profile.sanitize();
+        profile
+    });
+    (version, profile.map_err(bad))
+}
+
+/// Reads a profile file, starting afresh if there's none or it can't be
+/// read. Anything that's brought up to date or given up on is backed up
+/// first, as the next save writes over it.
+fn load_profile_file(ctx: &mut Context, path: &str) -> Profile {
+    if !ctx.filesystem.is_file(path) {
+        return Profile::default();
+    }
+    let mut contents = vec![];
+    let read = ctx.filesystem.open(path)
+        .and_then(|mut file| file.read_to_end(&mut contents).map_err(GameError::from));
+    let (version, loaded) = match read {
+        Ok(_) => read_profile(&contents, path),
+        Err(e) => (0, Err(e)),
+    };
+    if contents.len() > 0 && (version != PROFILE_VERSION || loaded.is_err()) {
+        back_up_profile(ctx, path, &contents, version);
+    }
+    match loaded {
+        Ok(profile) => profile,
+        Err(e) => {
+            println!("Could not read the profile, starting afresh");
+            println!("Error: {}", e);
+            Profile::default()
+        }
+    }
+}
+
+/// Reads a profile, starting a fresh one if there is none
+pub fn load_profile(ctx: &mut Context, id: u32) -> Profile {
+    load_profile_file(ctx, &profile_path(id))
+}
+
+pub fn save_profile(ctx: &mut Context, id: u32, profile: &Profile) -> GameResult<()> {
+    let json = serde_json::to_string_pretty(profile)
+        .map_err(|e| GameError::UnknownError(format!("Could not write the profile: {}", e)))?;
+    if !ctx.filesystem.is_dir(PROFILE_DIR) {
+        ctx.filesystem.create_dir(PROFILE_DIR)?;
+    }
+    let mut file = ctx.filesystem.create(profile_path(id))?;
+    file.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+/// Adds a fresh profile to the index and writes it out, under an id that
+/// has no file yet, so nothing is ever written over
+pub fn create_profile(ctx: &mut Context, index: &mut ProfileIndex, name: &str) -> GameResult<u32> {
+    loop {
+        let id = index.next_id;
+        if !ctx.filesystem.is_file(profile_path(id)) && index.profiles.iter().all(|entry| entry.id != id) {
+            break;
+        }
+        index.next_id += 1;
+    }
+    let id = index.add(name);
+    save_profile(ctx, id, &Profile::default())?;
+    Ok(id)
+}
+
+pub fn delete_profile(ctx: &mut Context, id: u32) -> GameResult<()> {
+    let path = profile_path(id);
+    if ctx.filesystem.is_file(&path) {
+        ctx.filesystem.delete(&path)?;
+    }
+    Ok(())
+}
+
+/// ********************************************************************
+/// Profile Index
+/// ********************************************************************
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileEntry {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileIndex {
+    pub profiles: Vec<ProfileEntry>,
+    /// Picked last time, and highlighted first this time
+    pub last: Option<u32>,
+    pub next_id: u32,
+}
+
+impl ProfileIndex {
+    /// Adds a profile under an id no other profile in the index has,
+    /// returning the id
+    pub fn add(&mut self, name: &str) -> u32 {
+        while self.profiles.iter().any(|entry| entry.id == self.next_id) {
+            self.next_id += 1;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.profiles.push(ProfileEntry { id: id, name: name.to_string() });
+        id
+    }
+
+    pub fn rename(&mut self, id: u32, name: &str) {
+        for entry in self.profiles.iter_mut() {
+            if entry.id == id {
+                entry.name = name.to_string();
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        self.profiles.retain(|entry| entry.id != id);
+        if self.last == Some(id) {
+            self.last = None;
+        }
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        self.profiles.iter().find(|entry| entry.id == id).map_or("", |entry| &entry.name)
+    }
+
+    /// A name like "Lord 3" that isn't taken yet
+    pub fn fresh_name(&self) -> String {
+        let mut n = self.profiles.len() + 1;
+        loop {
+            let name = format!("Lord {}", n);
+            if self.profiles.iter().all(|entry| entry.name != name) {
+                return name;
+            }
+            n += 1;
+        }
+    }
+}
+
+/// The id of a profile file, going by its name. Suspended runs and
+/// backups sit in the same directory, and aren't profiles.
+fn profile_file_id(path: &PathBuf) -> Option<u32> {
+    if path.extension().map_or(true, |ext| ext != "json") {
+        return None;
+    }
+    path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok())
+}
+
+/// An index of whatever profile files there are, for when the real one
+/// is lost. The names went with it, so they're made up from the ids.
+fn index_from_files(paths: &[PathBuf]) -> ProfileIndex {
+    let mut ids: Vec<u32> = paths.iter().filter_map(profile_file_id).collect();
+    ids.sort();
+    ids.dedup();
+    ProfileIndex {
+        profiles: ids.iter().map(|id| ProfileEntry { id: *id, name: format!("Lord {}", id + 1) }).collect(),
+        last: ids.first().cloned(),
+        next_id: ids.last().map_or(0, |id| id + 1),
+    }
+}
+
+fn profile_files(ctx: &mut Context) -> Vec<PathBuf> {
+    if !ctx.filesystem.is_dir(PROFILE_DIR) {
+        return vec![];
+    }
+    ctx.filesystem.read_dir(PROFILE_DIR).unwrap_or_default()
+}
+
+fn read_profile_index(contents: &[u8]) -> GameResult<ProfileIndex> {
+    serde_json::from_slice(contents)
+        .map_err(|e| GameError::ResourceLoadError(format!("Bad profile index: {}", e)))
+}
+
+/// Reads the index, setting it aside if it can't be, as it's about to be
+/// written over
+fn load_index_file(ctx: &mut Context) -> GameResult<ProfileIndex> {
+    let mut contents = vec![];
+    ctx.filesystem.open(PROFILE_INDEX_FILE)?.read_to_end(&mut contents)?;
+    let loaded = read_profile_index(&contents);
+    if loaded.is_err() {
+        let result = ctx.filesystem.create(BAD_INDEX_FILE)
+            .and_then(|mut file| file.write_all(&contents).map_err(GameError::from));
+        if let Err(e) = result {
+            println!("Could not back up the profile index to {}", BAD_INDEX_FILE);
+            println!("Error: {}", e);
+        }
+    }
+    loaded
+}
+
+pub fn save_profile_index(ctx: &mut Context, index: &ProfileIndex) -> GameResult<()> {
+    let json = serde_json::to_string_pretty(index)
+        .map_err(|e| GameError::UnknownError(format!("Could not write the profile index: {}", e)))?;
+    if !ctx.filesystem.is_dir(PROFILE_DIR) {
+        ctx.filesystem.create_dir(PROFILE_DIR)?;
+    }
+    let mut file = ctx.filesystem.create(PROFILE_INDEX_FILE)?;
+    file.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+/// The first time round there's no index. Whatever the single profile,
+/// high score and daily files had becomes the first profile.
+fn first_profile_index(ctx: &mut Context) -> GameResult<ProfileIndex> {
+    let mut index = ProfileIndex::default();
+    let mut profile = load_profile_file(ctx, LEGACY_PROFILE_FILE);
+    profile.high_scores = load_high_scores(ctx);
+    profile.daily_results = load_daily_results(ctx);
+    let name = index.fresh_name();
+    let id = index.add(&name);
+    index.last = Some(id);
+    save_profile(ctx, id, &profile)?;
+    save_profile_index(ctx, &index)?;
+    Ok(index)
+}
+
+/// Reads the profile index, making it if it isn't there yet. An index
+/// that's missing or can't be read while there are profile files is
+/// made again from the files. There is always at least one profile.
+pub fn load_profile_index(ctx: &mut Context) -> ProfileIndex {
+    let files = profile_files(ctx);
+    let loaded = if ctx.filesystem.is_file(PROFILE_INDEX_FILE) {
+        load_index_file(ctx)
+    }
+    else if files.iter().any(|path| profile_file_id(path).is_some()) {
+        Err(GameError::ResourceLoadError("The profile index is missing".to_string()))
+    }
+    else {
+        first_profile_index(ctx)
+    };
+    let mut index = match loaded {
+        Ok(index) => index,
+        Err(e) => {
+            println!("Could not read the profile index, making it again from the profile files");
+            println!("Error: {}", e);
+            let index = index_from_files(&files);
+            if let Err(e) = save_profile_index(ctx, &index) {
+                println!("Could not save the profile index");
+                println!("Error: {}", e);
+            }
+            index
+        }
+    };
+    // Never hand out an id that already has a file
+    let rebuilt = index_from_files(&files);
+    index.next_id = index.next_id.max(rebuilt.next_id);
+    if index.profiles.len() == 0 {
+        let name = index.fresh_name();
+        index.add(&name);
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "/profiles/1.json";
+
+    #[test]
+    fn old_profiles_are_stamped_with_the_current_version() {
+        let (version, profile) = read_profile(br#"{"version": 1, "total_friends": 7}"#, PATH);
+        assert_eq!(version, 1);
+        let profile = profile.unwrap();
+        assert_eq!(profile.version, PROFILE_VERSION);
+        assert_eq!(profile.total_friends, 7);
+        assert_eq!(profile.settings, Settings::default());
+    }
+
+    #[test]
+    fn unversioned_profiles_are_brought_up_to_date() {
+        let (version, profile) = read_profile(br#"{"total_friends": 60, "best_score": 12}"#, PATH);
+        assert_eq!(version, 0);
+        let profile = profile.unwrap();
+        assert_eq!(profile.version, PROFILE_VERSION);
+        assert_eq!((profile.total_friends, profile.best_score), (60, 12));
+        assert_eq!(profile.bindings, KeyBindings::default());
+        assert!(!profile.tutorial_done);
+    }
+
+    #[test]
+    fn current_profiles_read_as_they_are() {
+        let json = serde_json::to_vec(&Profile { total_friends: 400, skin: Skin::Royal, level: 2,
+                                                 tutorial_done: true, ..Profile::default() }).unwrap();
+        let (version, profile) = read_profile(&json, PATH);
+        assert_eq!(version, PROFILE_VERSION);
+        let profile = profile.unwrap();
+        assert_eq!((profile.total_friends, profile.skin, profile.level), (400, Skin::Royal, 2));
+        assert!(profile.tutorial_done);
+    }
+
+    #[test]
+    fn newer_profiles_are_refused() {
+        let (version, profile) = read_profile(br#"{"version": 99, "total_friends": 5}"#, PATH);
+        assert_eq!(version, 99);
+        assert!(profile.is_err());
+    }
+
+    #[test]
+    fn malformed_profiles_are_refused() {
+        let (version, profile) = read_profile(b"{\"version\": 2, \"total", PATH);
+        assert_eq!(version, 0);
+        assert!(profile.is_err());
+        // Right version, wrong shape
+        let (version, profile) = read_profile(br#"{"version": 2, "total_friends": "lots"}"#, PATH);
+        assert_eq!(version, PROFILE_VERSION);
+        assert!(profile.is_err());
+        let (_, profile) = read_profile(&[0xff, 0xfe, 0x00], PATH);
+        assert!(profile.is_err());
+    }
+
+    #[test]
+    fn picks_that_are_not_unlocked_are_dropped() {
+        let (_, profile) = read_profile(br#"{"version": 2, "level": 57, "skin": "Royal"}"#, PATH);
+        let profile = profile.unwrap();
+        assert_eq!((profile.level, profile.skin), (0, Skin::Classic));
+        let (_, profile) = read_profile(br#"{"version": 2, "level": 1, "total_friends": 10}"#, PATH);
+        assert_eq!(profile.unwrap().level, 0);
+    }
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(|name| PathBuf::from(format!("{}/{}", PROFILE_DIR, name))).collect()
+    }
+
+    #[test]
+    fn index_is_made_again_from_profile_files() {
+        let files = paths(&["index.json", "0.json", "3.json", "3_suspend.json", "1_v1.json",
+                            "index_bad.json", "7.txt", "2.json"]);
+        let index = index_from_files(&files);
+        let ids: Vec<u32> = index.profiles.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![0, 2, 3]);
+        assert_eq!(index.name(3), "Lord 4");
+        assert_eq!((index.last, index.next_id), (Some(0), 4));
+        let empty = index_from_files(&paths(&["index.json"]));
+        assert_eq!((empty.profiles.len(), empty.last, empty.next_id), (0, None, 0));
+    }
+
+    #[test]
+    fn added_profiles_never_share_an_id() {
+        let mut index = ProfileIndex {
+            profiles: vec![ProfileEntry { id: 0, name: "Lord 1".to_string() },
+                           ProfileEntry { id: 1, name: "Lord 2".to_string() }],
+            last: None,
+            next_id: 0,
+        };
+        let name = index.fresh_name();
+        assert_eq!(name, "Lord 3");
+        assert_eq!(index.add(&name), 2);
+        assert_eq!(index.add("Lord 4"), 3);
+        index.remove(3);
+        assert_eq!(index.add("Lord 5"), 4);
+    }
+
+    #[test]
+    fn bad_index_is_refused() {
+        assert!(read_profile_index(b"{\"profiles\": [").is_err());
+        let index = read_profile_index(br#"{"profiles": [{"id": 4, "name": "Ann"}], "last": 4, "next_id": 5}"#)
+            .unwrap();
+        assert_eq!((index.name(4), index.next_id), ("Ann", 5));
+    }
+}
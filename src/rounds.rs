@@ -0,0 +1,208 @@
+use difficulty::Difficulty;
+
+/// ********************************************************************
+/// Rounds and the Intermission Shop
+///
+/// Classic is played in rounds. Each round has a friend quota, and
+/// making it before the clock runs out stops the clock and opens the
+/// shop, where points earned so far buy upgrades for the rest of the
+/// run. Buying doesn't take anything off the score; the shop keeps its
+/// own tally of what has been spent.
+/// ********************************************************************
+
+/// Friends needed in the first round
+const BASE_QUOTA: u32 = 5;
+/// Extra friends needed every round after
+const QUOTA_STEP: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Upgrade {
+    /// Widens the hit window of every minigame
+    WiderWindow,
+    /// Walks faster
+    FasterMovement,
+    /// Bodies can be dropped into fire pits from further away
+    BiggerFire,
+    /// More time on the clock at the start of every round
+    ExtraTime,
+}
+
+pub const UPGRADES: [Upgrade; 4] = [
+    Upgrade::WiderWindow,
+    Upgrade::FasterMovement,
+    Upgrade::BiggerFire,
+    Upgrade::ExtraTime,
+];
+
+/// Most times a single upgrade can be bought
+pub const MAX_UPGRADE_LEVEL: u32 = 5;
+
+impl Upgrade {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Upgrade::WiderWindow => "Wider Hit Window",
+            Upgrade::FasterMovement => "Faster Movement",
+            Upgrade::BiggerFire => "Bigger Fire Radius",
+            Upgrade::ExtraTime => "Extra Time",
+        }
+    }
+
+    /// Cost of the next level, going up with every level bought
+    pub fn cost(&self, level: u32) -> u32 {
+        let base = match *self {
+            Upgrade::WiderWindow => 15,
+            Upgrade::FasterMovement => 10,
+            Upgrade::BiggerFire => 8,
+            Upgrade::ExtraTime => 12,
+        };
+        base * (level + 1)
+    }
+}
+
+/// Levels bought of each upgrade this run
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Upgrades {
+    pub levels: [u32; 4],
+}
+
+impl Upgrades {
+    pub fn level(&self, upgrade: Upgrade) -> u32 {
+        self.levels[upgrade as usize]
+    }
+
+    /// Scales the hit window, 15% a level
+    pub fn apply(&self, mut difficulty: Difficulty) -> Difficulty {
+        difficulty.hit_window *= 1.0 + 0.15 * self.level(Upgrade::WiderWindow) as f32;
+        difficulty
+    }
+
+    /// Multiplies the lord's speed, 10% a level
+    pub fn speed_scale(&self) -> f32 {
+        1.0 + 0.1 * self.level(Upgrade::FasterMovement) as f32
+    }
+
+    /// Multiplies how close to a fire pit a body has to be dropped, 25% a level
+    pub fn fire_range_scale(&self) -> f32 {
+        1.0 + 0.25 * self.level(Upgrade::BiggerFire) as f32
+    }
+
+    /// Seconds added at the start of every round, 10 a level
+    pub fn extra_time(&self) -> f32 {
+        10.0 * self.level(Upgrade::ExtraTime) as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Rounds {
+    /// Counting from one once the run starts
+    pub round: u32,
+    /// Friends made this round
+    pub friends: u32,
+    /// Points spent in the shop, out of the score
+    pub spent: u32,
+    pub upgrades: Upgrades,
+    /// Entry highlighted in the shop, one past the upgrades is "next round"
+    pub choice: usize,
+}
+
+impl Rounds {
+    /// Friends needed in the given round
+    pub fn quota_for(round: u32) -> u32 {
+        BASE_QUOTA + QUOTA_STEP * round.saturating_sub(1)
+    }
+
+    pub fn quota(&self) -> u32 {
+        Rounds::quota_for(self.round)
+    }
+
+    pub fn quota_met(&self) -> bool {
+        self.friends >= self.quota()
+    }
+
+    /// Points left to spend out of the score
+    pub fn wallet(&self, score: u32) -> u32 {
+        score.saturating_sub(self.spent)
+    }
+
+    /// Starts the next round, returning the seconds to put on the clock
+    pub fn next_round(&mut self, start_time: f32) -> f32 {
+        self.round += 1;
+        self.friends = 0;
+        self.choice = 0;
+        start_time + self.upgrades.extra_time()
+    }
+
+    /// Buys the next level of an upgrade if there are points enough,
+    /// returning whether it was bought
+    pub fn buy(&mut self, upgrade: Upgrade, score: u32) -> bool {
+        let level = self.upgrades.level(upgrade);
+        let cost = upgrade.cost(level);
+        if level >= MAX_UPGRADE_LEVEL || cost > self.wallet(score) {
+            return false;
+        }
+        self.spent += cost;
+        self.upgrades.levels[upgrade as usize] += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_goes_up_every_round() {
+        assert_eq!(Rounds::quota_for(0), BASE_QUOTA);
+        assert_eq!(Rounds::quota_for(1), BASE_QUOTA);
+        assert_eq!(Rounds::quota_for(2), BASE_QUOTA + QUOTA_STEP);
+        assert_eq!(Rounds::quota_for(4), BASE_QUOTA + 3 * QUOTA_STEP);
+    }
+
+    #[test]
+    fn next_round_starts_the_count_again() {
+        let mut rounds = Rounds::default();
+        assert_eq!(rounds.next_round(60.0), 60.0);
+        assert_eq!(rounds.round, 1);
+        rounds.friends = rounds.quota() - 1;
+        assert!(!rounds.quota_met());
+        rounds.friends += 1;
+        assert!(rounds.quota_met());
+        rounds.upgrades.levels[Upgrade::ExtraTime as usize] = 2;
+        rounds.choice = 3;
+        assert_eq!(rounds.next_round(60.0), 80.0);
+        assert_eq!((rounds.round, rounds.friends, rounds.choice), (2, 0, 0));
+        assert_eq!(rounds.quota(), BASE_QUOTA + QUOTA_STEP);
+    }
+
+    #[test]
+    fn buying_spends_out_of_the_score() {
+        let mut rounds = Rounds::default();
+        assert!(!rounds.buy(Upgrade::FasterMovement, 9));
+        assert!(rounds.buy(Upgrade::FasterMovement, 30));
+        assert_eq!((rounds.spent, rounds.wallet(30)), (10, 20));
+        assert!(rounds.buy(Upgrade::FasterMovement, 30));
+        assert_eq!((rounds.spent, rounds.wallet(30)), (30, 0));
+        assert!(!rounds.buy(Upgrade::BiggerFire, 30));
+        assert_eq!(rounds.upgrades.level(Upgrade::FasterMovement), 2);
+        assert_eq!(rounds.upgrades.level(Upgrade::BiggerFire), 0);
+        assert!((rounds.upgrades.speed_scale() - 1.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn upgrades_stop_at_the_top_level() {
+        let mut rounds = Rounds::default();
+        for _ in 0..MAX_UPGRADE_LEVEL {
+            assert!(rounds.buy(Upgrade::BiggerFire, 10000));
+        }
+        let spent = rounds.spent;
+        assert!(!rounds.buy(Upgrade::BiggerFire, 10000));
+        assert_eq!(rounds.spent, spent);
+        assert_eq!(rounds.upgrades.level(Upgrade::BiggerFire), MAX_UPGRADE_LEVEL);
+    }
+
+    #[test]
+    fn wallet_never_goes_below_nothing() {
+        let rounds = Rounds { spent: 50, ..Rounds::default() };
+        assert_eq!(rounds.wallet(20), 0);
+    }
+}
@@ -0,0 +1,289 @@
+use archetype::Archetype;
+use minigame::Judgement;
+
+/// ********************************************************************
+/// Scoring
+///
+/// Every change to the score goes through here as a `ScoreEvent`, which
+/// is added to the running total and to its line on the end screen, and
+/// queued up for the HUD to show. A high five is worth:
+///
+/// * the minion's archetype points for every pass landed
+/// * a bonus for how close the worst pass was to the goal
+/// * all of that times the combo multiplier
+/// * a bonus for having no bodies lying around at the time
+/// * a share of the above on top for the mutators switched on
+///
+/// Penalties take points back off, but never below zero.
+/// ********************************************************************
+
+/// Every `COMBO_STEP` passes in a row adds one to the multiplier
+pub const COMBO_STEP: u32 = 3;
+pub const COMBO_MAX_MULTIPLIER: u32 = 5;
+/// Points for a high five with no bodies lying around
+const CLEAN_FLOOR_BONUS: u32 = 1;
+/// Points at the end of a run with no bodies lying around
+const CLEAN_FINISH_BONUS: u32 = 10;
+
+/// How close a high five was, as a share of the hit window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Tier {
+    Perfect,
+    Great,
+    Good,
+}
+
+impl Tier {
+    pub fn from_judgement(judgement: &Judgement) -> Tier {
+        let share = judgement.error / judgement.window;
+        if share <= 0.25 {
+            Tier::Perfect
+        }
+        else if share <= 0.6 {
+            Tier::Great
+        }
+        else {
+            Tier::Good
+        }
+    }
+
+    pub fn bonus(&self) -> u32 {
+        match *self {
+            Tier::Perfect => 2,
+            Tier::Great => 1,
+            Tier::Good => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Penalty {
+    /// A minion saw something it shouldn't have
+    Witness,
+    /// A hidden body was found and dragged back out
+    BodyFound,
+}
+
+impl Penalty {
+    pub fn points(&self) -> u32 {
+        match *self {
+            Penalty::Witness => 2,
+            Penalty::BodyFound => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScoreEvent {
+    HighFive { archetype: Archetype, points: u32 },
+    TierBonus { tier: Tier, points: u32 },
+    ComboBonus { multiplier: u32, points: u32 },
+    CleanFloor { points: u32 },
+    MutatorBonus { points: u32 },
+    CleanFinish { points: u32 },
+    Penalty { penalty: Penalty, points: u32 },
+}
+
+impl ScoreEvent {
+    /// How the event reads when it pops up on the HUD
+    pub fn describe(&self) -> String {
+        match *self {
+            ScoreEvent::HighFive { archetype, points } => format!("+{} {}", points, archetype.info().name),
+            ScoreEvent::TierBonus { tier, points } => format!("+{} {:?}", points, tier),
+            ScoreEvent::ComboBonus { multiplier, points } => format!("+{} Combo x{}", points, multiplier),
+            ScoreEvent::CleanFloor { points } => format!("+{} Clean Floor", points),
+            ScoreEvent::MutatorBonus { points } => format!("+{} Mutators", points),
+            ScoreEvent::CleanFinish { points } => format!("+{} Clean Finish", points),
+            ScoreEvent::Penalty { penalty, points } => format!("-{} {:?}", points, penalty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Scoring {
+    pub total: u32,
+    /// Passes landed in a row, across minions, until a miss
+    pub combo: u32,
+    pub best_combo: u32,
+    pub base: u32,
+    pub tier_bonus: u32,
+    pub combo_bonus: u32,
+    pub clean_bonus: u32,
+    pub penalties: u32,
+    pub perfects: u32,
+    pub mutator_bonus: u32,
+    /// From the run's mutators, see `Mutators::score_multiplier`
+    pub mutator_multiplier: f32,
+    /// Events not yet shown on the HUD
+    pub events: Vec<ScoreEvent>,
+}
+
+impl Scoring {
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_STEP).min(COMBO_MAX_MULTIPLIER)
+    }
+
+    fn award(&mut self, event: ScoreEvent) {
+        let event = match event {
+            ScoreEvent::HighFive { points, .. } => {
+                self.base += points;
+                self.total += points;
+                event
+            }
+            ScoreEvent::TierBonus { points, .. } => {
+                self.tier_bonus += points;
+                self.total += points;
+                event
+            }
+            ScoreEvent::ComboBonus { points, .. } => {
+                self.combo_bonus += points;
+                self.total += points;
+                event
+            }
+            ScoreEvent::MutatorBonus { points } => {
+                self.mutator_bonus += points;
+                self.total += points;
+                event
+            }
+            ScoreEvent::CleanFloor { points } | ScoreEvent::CleanFinish { points } => {
+                self.clean_bonus += points;
+                self.total += points;
+                event
+            }
+            ScoreEvent::Penalty { penalty, points } => {
+                // Only what was actually taken off goes on the breakdown
+                let points = points.min(self.total);
+                self.penalties += points;
+                self.total -= points;
+                ScoreEvent::Penalty { penalty: penalty, points: points }
+            }
+        };
+        self.events.push(event);
+    }
+
+    /// Scores a landed high five. `clean` is whether there were no bodies
+    /// lying around when it happened.
+    pub fn high_five(&mut self, archetype: Archetype, judgement: &Judgement, clean: bool) {
+        self.combo += judgement.passes;
+        self.best_combo = self.best_combo.max(self.combo);
+        let tier = Tier::from_judgement(judgement);
+        if tier == Tier::Perfect {
+            self.perfects += 1;
+        }
+        let base = archetype.info().score * judgement.passes;
+        let bonus = tier.bonus();
+        let multiplier = self.multiplier();
+        self.award(ScoreEvent::HighFive { archetype: archetype, points: base });
+        if bonus > 0 {
+            self.award(ScoreEvent::TierBonus { tier: tier, points: bonus });
+        }
+        if multiplier > 1 {
+            self.award(ScoreEvent::ComboBonus { multiplier: multiplier, points: (base + bonus) * (multiplier - 1) });
+        }
+        if clean {
+            self.award(ScoreEvent::CleanFloor { points: CLEAN_FLOOR_BONUS });
+        }
+        if self.mutator_multiplier > 1.0 {
+            let earned = (base + bonus) * multiplier;
+            let points = (earned as f32 * (self.mutator_multiplier - 1.0)).round() as u32;
+            if points > 0 {
+                self.award(ScoreEvent::MutatorBonus { points: points });
+            }
+        }
+    }
+
+    pub fn miss(&mut self) {
+        self.combo = 0;
+    }
+
+    pub fn penalty(&mut self, penalty: Penalty) {
+        self.award(ScoreEvent::Penalty { penalty: penalty, points: penalty.points() });
+    }
+
+    /// Called once when the run ends
+    pub fn finish(&mut self, clean: bool) {
+        if clean {
+            self.award(ScoreEvent::CleanFinish { points: CLEAN_FINISH_BONUS });
+        }
+    }
+
+    /// Lines for the end screen, one per kind of points
+    pub fn breakdown(&self) -> Vec<String> {
+        vec![
+            format!("High Fives: {}", self.base),
+            format!("Timing Bonus: {} ({} Perfect)", self.tier_bonus, self.perfects),
+            format!("Combo Bonus: {} (Best Streak {})", self.combo_bonus, self.best_combo),
+            format!("Clean Floor: {}", self.clean_bonus),
+            format!("Mutators: {} (x{:.2})", self.mutator_bonus, self.mutator_multiplier.max(1.0)),
+            format!("Penalties: -{}", self.penalties),
+            format!("Total: {}", self.total),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn judgement(error: f32, passes: u32) -> Judgement {
+        Judgement { error: error, window: 0.1, time: 1.0, speed: 1.0, passes: passes }
+    }
+
+    #[test]
+    fn tiers_by_share_of_the_window() {
+        assert_eq!(Tier::from_judgement(&judgement(0.0, 1)), Tier::Perfect);
+        assert_eq!(Tier::from_judgement(&judgement(0.025, 1)), Tier::Perfect);
+        assert_eq!(Tier::from_judgement(&judgement(0.03, 1)), Tier::Great);
+        assert_eq!(Tier::from_judgement(&judgement(0.06, 1)), Tier::Great);
+        assert_eq!(Tier::from_judgement(&judgement(0.07, 1)), Tier::Good);
+        assert_eq!(Tier::from_judgement(&judgement(0.1, 1)), Tier::Good);
+    }
+
+    #[test]
+    fn high_five_adds_up_by_tier() {
+        let mut scoring = Scoring::default();
+        scoring.high_five(Archetype::Regular, &judgement(0.0, 1), false);
+        assert_eq!((scoring.base, scoring.tier_bonus, scoring.total), (1, 2, 3));
+        assert_eq!(scoring.perfects, 1);
+        scoring.high_five(Archetype::Regular, &judgement(0.05, 1), false);
+        assert_eq!((scoring.base, scoring.tier_bonus, scoring.total), (2, 3, 5));
+        scoring.high_five(Archetype::Regular, &judgement(0.09, 1), true);
+        assert_eq!((scoring.base, scoring.tier_bonus, scoring.clean_bonus), (3, 3, 1));
+        assert_eq!(scoring.perfects, 1);
+        assert_eq!(scoring.events.iter().filter(|e| match **e {
+            ScoreEvent::TierBonus { .. } => true,
+            _ => false,
+        }).count(), 2);
+    }
+
+    #[test]
+    fn combo_multiplies_and_a_miss_resets_it() {
+        let mut scoring = Scoring::default();
+        scoring.high_five(Archetype::Regular, &judgement(0.09, COMBO_STEP), false);
+        assert_eq!(scoring.multiplier(), 2);
+        assert_eq!((scoring.base, scoring.combo_bonus), (COMBO_STEP, COMBO_STEP));
+        scoring.miss();
+        assert_eq!((scoring.combo, scoring.best_combo, scoring.multiplier()), (0, COMBO_STEP, 1));
+        scoring.combo = 100;
+        assert_eq!(scoring.multiplier(), COMBO_MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn penalties_stop_at_zero() {
+        let mut scoring = Scoring::default();
+        scoring.high_five(Archetype::Regular, &judgement(0.09, 1), false);
+        scoring.penalty(Penalty::BodyFound);
+        assert_eq!((scoring.total, scoring.penalties), (0, 1));
+        assert_eq!(scoring.events.last(), Some(&ScoreEvent::Penalty { penalty: Penalty::BodyFound, points: 1 }));
+    }
+
+    #[test]
+    fn mutators_add_their_share() {
+        let mut scoring = Scoring { mutator_multiplier: 1.5, ..Scoring::default() };
+        scoring.high_five(Archetype::Grumpy, &judgement(0.0, 1), false);
+        assert_eq!((scoring.base, scoring.tier_bonus, scoring.mutator_bonus), (3, 2, 3));
+        assert_eq!(scoring.total, 8);
+        scoring.finish(true);
+        assert_eq!(scoring.total, 8 + CLEAN_FINISH_BONUS);
+    }
+}
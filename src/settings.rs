@@ -0,0 +1,304 @@
+use ggez::{Context, GameResult};
+use ggez::event::Keycode;
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point};
+
+use {MainState, draw_text};
+
+/// ********************************************************************
+/// Settings and Key Bindings
+///
+/// Both belong to a profile. Bindings are kept as key names, the way
+/// SDL spells them, so the profile file stays readable and a key this
+/// build doesn't know about is just skipped.
+///
+/// The settings screen is state 13. It goes back to whichever state
+/// opened it.
+/// ********************************************************************
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Points popping up under the score
+    pub score_popups: bool,
+    /// Straight from the title screen into the game, even before the
+    /// tutorial has been played
+    pub skip_intro: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            score_popups: true,
+            skip_intro: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+    PickUp,
+    Drop,
+}
+
+pub const ACTIONS: [Action; 7] = [
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::Fire,
+    Action::PickUp,
+    Action::Drop,
+];
+
+/// Keys that already do something everywhere, and can't be bound
+const RESERVED_KEYS: [Keycode; 4] = [Keycode::Escape, Keycode::F2, Keycode::Tab, Keycode::Backspace];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Fire => "High Five",
+            Action::PickUp => "Pick Up Body",
+            Action::Drop => "Drop Body",
+        }
+    }
+}
+
+/// Key names for every action, first one first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub fire: Vec<String>,
+    pub pick_up: Vec<String>,
+    pub drop: Vec<String>,
+}
+
+fn key_names(keys: &[Keycode]) -> Vec<String> {
+    keys.iter().map(|key| key.name()).collect()
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            up: key_names(&[Keycode::W, Keycode::Up]),
+            down: key_names(&[Keycode::S, Keycode::Down]),
+            left: key_names(&[Keycode::A, Keycode::Left]),
+            right: key_names(&[Keycode::D, Keycode::Right]),
+            fire: key_names(&[Keycode::Space, Keycode::Return]),
+            pick_up: key_names(&[Keycode::E]),
+            drop: key_names(&[Keycode::Q]),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &Vec<String> {
+        match action {
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::Fire => &self.fire,
+            Action::PickUp => &self.pick_up,
+            Action::Drop => &self.drop,
+        }
+    }
+
+    fn keys_mut(&mut self, action: Action) -> &mut Vec<String> {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Fire => &mut self.fire,
+            Action::PickUp => &mut self.pick_up,
+            Action::Drop => &mut self.drop,
+        }
+    }
+
+    /// The action a key is bound to, if any
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        let name = keycode.name();
+        ACTIONS.iter().cloned().find(|action| self.keys(*action).contains(&name))
+    }
+
+    /// Makes a key the first one for an action, taking it off any other.
+    /// Returns false for keys that can't be bound.
+    pub fn rebind(&mut self, action: Action, keycode: Keycode) -> bool {
+        if RESERVED_KEYS.contains(&keycode) {
+            return false;
+        }
+        let name = keycode.name();
+        for other in ACTIONS.iter() {
+            self.keys_mut(*other).retain(|key| *key != name);
+        }
+        let keys = self.keys_mut(action);
+        if keys.len() > 0 {
+            keys.remove(0);
+        }
+        keys.insert(0, name);
+        true
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SettingsMenu {
+    pub choice: usize,
+    /// Waiting on a key for the highlighted action
+    pub waiting: bool,
+    /// State to go back to
+    pub return_state: u32,
+}
+
+/// Settings lines come before the bindings, and "Back" is last
+const SETTING_LINES: usize = 2;
+
+fn menu_len() -> usize {
+    SETTING_LINES + ACTIONS.len() + 1
+}
+
+pub fn open_settings(game: &mut MainState) {
+    game.settings_menu = SettingsMenu {
+        choice: 0,
+        waiting: false,
+        return_state: game.state,
+    };
+    game.state = 13;
+}
+
+fn close_settings(game: &mut MainState) {
+    game.state = game.settings_menu.return_state;
+    game.settings_menu.waiting = false;
+    game.profile_dirty = true;
+}
+
+/// Keys on the settings screen. These go by the keys themselves rather
+/// than the bindings, so a bad binding can always be fixed.
+pub fn settings_key(game: &mut MainState, keycode: Keycode) {
+    let len = menu_len();
+    if game.settings_menu.waiting {
+        let action = ACTIONS[game.settings_menu.choice - SETTING_LINES];
+        if game.profile.bindings.rebind(action, keycode) {
+            game.settings_menu.waiting = false;
+        }
+        return;
+    }
+    match keycode {
+        Keycode::Up | Keycode::W => {
+            game.settings_menu.choice = (game.settings_menu.choice + len - 1) % len;
+        }
+        Keycode::Down | Keycode::S => {
+            game.settings_menu.choice = (game.settings_menu.choice + 1) % len;
+        }
+        Keycode::Backspace => close_settings(game),
+        Keycode::Return | Keycode::Space | Keycode::Left | Keycode::Right => {
+            match game.settings_menu.choice {
+                0 => game.profile.settings.score_popups = !game.profile.settings.score_popups,
+                1 => game.profile.settings.skip_intro = !game.profile.settings.skip_intro,
+                x if x == len - 1 => close_settings(game),
+                _ => game.settings_menu.waiting = true,
+            }
+        }
+        _ => (),
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "On" } else { "Off" }
+}
+
+pub fn draw_settings(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let center = Point::new((game.screen_width / 2) as f32, (game.screen_height / 2) as f32);
+    graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.9))?;
+    graphics::rectangle(ctx, DrawMode::Fill, graphics::Rect::new(center.x, center.y, 560.0, 440.0))?;
+    graphics::set_color(ctx, graphics::BLACK)?;
+    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+    let mut lines = vec![
+        format!("Score Popups: {}", on_off(game.profile.settings.score_popups)),
+        format!("Skip Tutorial: {}", on_off(game.profile.settings.skip_intro)),
+    ];
+    for x in 0..ACTIONS.len() {
+        let action = ACTIONS[x];
+        let keys = if game.settings_menu.waiting && game.settings_menu.choice == SETTING_LINES + x {
+            "Press A Key...".to_string()
+        }
+        else {
+            game.profile.bindings.keys(action).join(", ")
+        };
+        lines.push(format!("{}: {}", action.name(), keys));
+    }
+    lines.push("Back".to_string());
+    let title = format!("Settings For {}", game.profiles.name(game.profile_id));
+    let mut title_text = graphics::Text::new(ctx, &title, font)?;
+    draw_text(ctx, &mut title_text, Point::new(center.x, center.y - 190.0))?;
+    for x in 0..lines.len() {
+        let marker = if x == game.settings_menu.choice { "> " } else { "" };
+        let line = format!("{}{}", marker, lines[x]);
+        let mut line_text = graphics::Text::new(ctx, &line, font)?;
+        let line_dest = Point::new(center.x - 250.0 + (line_text.width() / 2) as f32,
+                                   center.y - 150.0 + 30.0 * x as f32);
+        draw_text(ctx, &mut line_text, line_dest)?;
+    }
+    let mut help_text = graphics::Text::new(ctx, "Enter Changes, Backspace Goes Back", font)?;
+    draw_text(ctx, &mut help_text, Point::new(center.x, center.y + 195.0))?;
+    graphics::set_color(ctx, graphics::WHITE)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_moves_the_key_and_keeps_the_spare() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.rebind(Action::Fire, Keycode::F));
+        assert_eq!(bindings.fire, vec![Keycode::F.name(), Keycode::Return.name()]);
+        assert_eq!(bindings.action(Keycode::F), Some(Action::Fire));
+        assert_eq!(bindings.action(Keycode::Space), None);
+    }
+
+    #[test]
+    fn rebinding_takes_the_key_off_other_actions() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.rebind(Action::Drop, Keycode::W));
+        assert_eq!(bindings.up, vec![Keycode::Up.name()]);
+        assert_eq!(bindings.drop, vec![Keycode::W.name()]);
+        assert_eq!(bindings.action(Keycode::W), Some(Action::Drop));
+        // Taking a key back leaves the action it came from with nothing
+        assert!(bindings.rebind(Action::PickUp, Keycode::W));
+        assert!(bindings.drop.is_empty());
+        assert_eq!(bindings.pick_up, vec![Keycode::W.name()]);
+    }
+
+    #[test]
+    fn rebinding_to_an_action_already_on_the_key() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.rebind(Action::Up, Keycode::Up));
+        assert_eq!(bindings.up, vec![Keycode::Up.name()]);
+        assert!(bindings.rebind(Action::Up, Keycode::W));
+        assert_eq!(bindings.up, vec![Keycode::W.name()]);
+    }
+
+    #[test]
+    fn reserved_keys_cannot_be_bound() {
+        let mut bindings = KeyBindings::default();
+        for key in RESERVED_KEYS.iter() {
+            assert!(!bindings.rebind(Action::Fire, *key));
+        }
+        assert_eq!(bindings, KeyBindings::default());
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use na::geometry::Point2;
+use rand::Rng;
+
+use level::Level;
+
+/// ********************************************************************
+/// Minion Spawning
+///
+/// New minions are dropped at random spots, rejecting any spot too close
+/// to a minion that's already there. Existing minions are bucketed into a
+/// grid with cells the size of the spacing box, so each candidate only
+/// has to be tested against the 3x3 cells around it rather than against
+/// every minion, and the number of candidates tried is capped so a full
+/// courtyard can't hang the game.
+/// ********************************************************************
+
+/// No two minions stand closer than this along each axis
+pub const MINION_SPACING_X: f32 = 170.0;
+pub const MINION_SPACING_Y: f32 = 310.0;
+/// Candidates tried before giving up on a spawn
+pub const SPAWN_ATTEMPTS: u32 = 30;
+
+pub struct SpatialGrid {
+    cell_width: f32,
+    cell_height: f32,
+    cells: HashMap<(i32, i32), Vec<Point2<f32>>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_width: f32, cell_height: f32) -> SpatialGrid {
+        SpatialGrid {
+            cell_width: cell_width,
+            cell_height: cell_height,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, point: &Point2<f32>) -> (i32, i32) {
+        ((point.x / self.cell_width).floor() as i32,
+         (point.y / self.cell_height).floor() as i32)
+    }
+
+    pub fn insert(&mut self, point: Point2<f32>) {
+        let cell = self.cell(&point);
+        self.cells.entry(cell).or_insert_with(Vec::new).push(point);
+    }
+
+    /// Whether any point in the grid is within a cell's size of `point`
+    /// along both axes
+    pub fn crowded(&self, point: &Point2<f32>) -> bool {
+        let (cx, cy) = self.cell(point);
+        for x in cx - 1..cx + 2 {
+            for y in cy - 1..cy + 2 {
+                if let Some(points) = self.cells.get(&(x, y)) {
+                    for other in points.iter() {
+                        if (other.x - point.x).abs() < self.cell_width
+                            && (other.y - point.y).abs() < self.cell_height {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Looks for a spot in the level's spawn regions clear of every point in
+/// `taken`. Gives up with `None` after `SPAWN_ATTEMPTS` tries, which
+/// in practice means there's no room left.
+pub fn find_spawn_point<R: Rng>(level: &Level, taken: &[Point2<f32>], rng: &mut R) -> Option<Point2<f32>> {
+    let mut grid = SpatialGrid::new(MINION_SPACING_X, MINION_SPACING_Y);
+    for point in taken.iter() {
+        grid.insert(*point);
+    }
+    for _ in 0..SPAWN_ATTEMPTS {
+        let spot = level.random_spawn_point(rng);
+        if level.can_spawn_at(&spot) && !grid.crowded(&spot) {
+            return Some(spot);
+        }
+    }
+    None
+}
@@ -0,0 +1,505 @@
+use std::io::{Read, Write};
+
+use ggez::{Context, GameResult, GameError};
+use rand::Rng;
+use serde_json;
+
+use achievements::AchievementTracker;
+use archetype::Archetype;
+use difficulty::{Difficulty, SkillTracker};
+use level::RingParams;
+use minigame::MinigameKind;
+use mode::GameMode;
+use mutators::Mutators;
+use profile::PROFILE_DIR;
+use rounds::Rounds;
+use scoring::Scoring;
+use {MainState, Actor, DisposalSite, switch_level, start_high_five};
+
+/// ********************************************************************
+/// Suspended Runs
+///
+/// Quitting in the middle of a run writes everything the simulation
+/// needs to a suspend file next to the profile, and the title screen
+/// offers to carry on from it next time. A run is only ever continued
+/// once: the file is deleted when it's picked back up, or when a new
+/// run is started instead.
+///
+/// Only the simulation is kept. The level comes back from its file, and
+/// a minigame that was under way starts over, rolled from the same seed
+/// so it's the same ring as before.
+/// ********************************************************************
+
+/// Suspend files from another version are thrown away, not migrated
+pub const SUSPEND_VERSION: u32 = 1;
+
+/// A generator whose state can be written out, which `ThreadRng` can't.
+/// Xorshift128, seeded through SplitMix64.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimRng {
+    state: [u32; 4],
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        let mut x = seed;
+        let mut state = [0; 4];
+        for word in state.iter_mut() {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *word = (z ^ (z >> 31)) as u32;
+        }
+        // Xorshift never gets out of all zeroes
+        if state == [0; 4] {
+            state[0] = 1;
+        }
+        SimRng { state: state }
+    }
+}
+
+impl Rng for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        let t = self.state[0] ^ (self.state[0] << 11);
+        self.state[0] = self.state[1];
+        self.state[1] = self.state[2];
+        self.state[2] = self.state[3];
+        self.state[3] = self.state[3] ^ (self.state[3] >> 19) ^ (t ^ (t >> 8));
+        self.state[3]
+    }
+}
+
+/// Serde for nalgebra points as `[x, y]`, nalgebra being built without
+/// its own
+pub mod point2 {
+    use na::geometry::Point2;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+    pub fn serialize<S: Serializer>(point: &Point2<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        (point.x, point.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point2<f32>, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Point2::new(x, y))
+    }
+}
+
+/// Serde for nalgebra vectors as `[x, y]`
+pub mod vector2 {
+    use na::core::Vector2;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+    pub fn serialize<S: Serializer>(vector: &Vector2<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        (vector.x, vector.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vector2<f32>, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Vector2::new(x, y))
+    }
+}
+
+/// Serde for ggez points as `[x, y]`
+pub mod point {
+    use ggez::graphics::Point;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+    pub fn serialize<S: Serializer>(point: &Point, serializer: S) -> Result<S::Ok, S::Error> {
+        (point.x, point.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Point::new(x, y))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suspended {
+    pub version: u32,
+    pub state: u32,
+    pub mode: GameMode,
+    pub mutators: Mutators,
+    pub level_index: usize,
+    pub player: Actor,
+    pub minions: Vec<Actor>,
+    pub dead_minions: Vec<Actor>,
+    pub sites: Vec<DisposalSite>,
+    pub inspector: Actor,
+    pub inspector_waypoint: usize,
+    pub success_five: Actor,
+    pub carrying: bool,
+    pub witnesses: u32,
+    pub timer: f32,
+    pub fire_move_timer: f32,
+    pub scoring: Scoring,
+    pub rounds: Rounds,
+    pub difficulty: Difficulty,
+    pub skill: SkillTracker,
+    pub partner: Archetype,
+    pub high_five_kind: Option<MinigameKind>,
+    pub high_five_ring: Option<RingParams>,
+    pub friends: u32,
+    pub kills: u32,
+    pub achievements: AchievementTracker,
+    pub rng: SimRng,
+    pub seed: u32,
+    pub spawns: u32,
+    pub minigames: u32,
+    /// The day a daily challenge was started on, which it still counts for
+    pub today: u32,
+}
+
+impl Suspended {
+    /// How the run reads on the title screen
+    pub fn describe(&self) -> String {
+        format!("{}, {} Friends, {} Points", self.mode.name(), self.friends, self.scoring.total)
+    }
+}
+
+/// Whether a state is the middle of a run, and worth suspending
+pub fn in_run(state: u32) -> bool {
+    match state {
+        0 | 1 | 2 | 11 => true,
+        _ => false,
+    }
+}
+
+pub fn suspend_path(profile_id: u32) -> String {
+    format!("{}/{}_suspend.json", PROFILE_DIR, profile_id)
+}
+
+/// Takes the run out of the game to be written. `MainState` is taken
+/// apart by name, so a field added to it doesn't build until it's either
+/// kept in `Suspended` or passed over below.
+pub fn capture(game: &MainState) -> Suspended {
+    let MainState {
+        state,
+        mode,
+        mutators,
+        level_index,
+        ref player,
+        ref minions,
+        ref dead_minions,
+        ref sites,
+        ref inspector,
+        inspector_waypoint,
+        ref success_five,
+        carrying,
+        witnesses,
+        timer,
+        fire_move_timer,
+        ref scoring,
+        rounds,
+        difficulty,
+        ref skill,
+        partner,
+        high_five_kind,
+        high_five_ring,
+        friends,
+        kills,
+        ref achievements,
+        ref rng,
+        seed,
+        spawns,
+        minigames,
+        today,
+        // The level and everything drawn or picked on screen come back
+        // from their own files or are made again
+        level: _,
+        inspector_route: _,
+        editor: _,
+        attention: _,
+        body_reminder: _,
+        minigame: _,
+        judgement: _,
+        caught: _,
+        won: _,
+        score_popups: _,
+        score_display: _,
+        timer_display: _,
+        toasts: _,
+        spawns_full: _,
+        // What's left belongs to the session rather than the run
+        screen_width: _,
+        screen_height: _,
+        input: _,
+        assets: _,
+        mode_choice: _,
+        high_score_place: _,
+        daily_best: _,
+        profile: _,
+        profiles: _,
+        profile_id: _,
+        picker: _,
+        settings_menu: _,
+        pause_menu: _,
+        new_unlocks: _,
+        profile_dirty: _,
+        suspended: _,
+        quit_requested: _,
+        suspend_written: _,
+        tutorial: _,
+    } = *game;
+    Suspended {
+        version: SUSPEND_VERSION,
+        state: state,
+        mode: mode,
+        mutators: mutators,
+        level_index: level_index,
+        player: player.clone(),
+        minions: minions.clone(),
+        dead_minions: dead_minions.clone(),
+        sites: sites.clone(),
+        inspector: inspector.clone(),
+        inspector_waypoint: inspector_waypoint,
+        success_five: success_five.clone(),
+        carrying: carrying,
+        witnesses: witnesses,
+        timer: timer,
+        fire_move_timer: fire_move_timer,
+        scoring: scoring.clone(),
+        rounds: rounds,
+        difficulty: difficulty,
+        skill: skill.clone(),
+        partner: partner,
+        high_five_kind: high_five_kind,
+        high_five_ring: high_five_ring,
+        friends: friends,
+        kills: kills,
+        achievements: achievements.clone(),
+        rng: rng.clone(),
+        seed: seed,
+        spawns: spawns,
+        minigames: minigames,
+        today: today,
+    }
+}
+
+/// Puts a suspended run back, picking up in the state it was left in.
+/// Every field is taken apart by name, so one added to `Suspended` and
+/// not put back here doesn't build.
+pub fn restore(game: &mut MainState, ctx: &mut Context, run: Suspended) -> GameResult<()> {
+    let Suspended {
+        version: _,
+        state,
+        mode,
+        mutators,
+        level_index,
+        player,
+        minions,
+        dead_minions,
+        sites,
+        inspector,
+        inspector_waypoint,
+        success_five,
+        carrying,
+        witnesses,
+        timer,
+        fire_move_timer,
+        scoring,
+        rounds,
+        difficulty,
+        skill,
+        partner,
+        high_five_kind,
+        high_five_ring,
+        friends,
+        kills,
+        achievements,
+        rng,
+        seed,
+        spawns,
+        minigames,
+        today,
+    } = run;
+    if game.level_index != level_index {
+        switch_level(game, ctx, level_index)?;
+    }
+    game.mode = mode;
+    game.mutators = mutators;
+    game.player = player;
+    game.minions = minions;
+    game.dead_minions = dead_minions;
+    game.sites = sites;
+    game.inspector = inspector;
+    game.inspector_waypoint = inspector_waypoint;
+    game.success_five = success_five;
+    game.carrying = carrying;
+    game.witnesses = witnesses;
+    game.timer = timer;
+    game.fire_move_timer = fire_move_timer;
+    game.scoring = scoring;
+    game.rounds = rounds;
+    game.difficulty = difficulty;
+    game.skill = skill;
+    game.partner = partner;
+    game.high_five_kind = high_five_kind;
+    game.high_five_ring = high_five_ring;
+    game.friends = friends;
+    game.kills = kills;
+    game.achievements = achievements;
+    game.rng = rng;
+    game.seed = seed;
+    game.spawns = spawns;
+    game.minigames = minigames;
+    game.today = today;
+    game.state = state;
+    if game.state == 1 {
+        // Roll the same minigame again, from the top
+        game.minigames -= 1;
+        start_high_five(game);
+    }
+    Ok(())
+}
+
+fn read_suspended(ctx: &mut Context, path: &str) -> GameResult<Suspended> {
+    let mut file = ctx.filesystem.open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    parse_suspended(&contents, path)
+}
+
+fn parse_suspended(contents: &str, path: &str) -> GameResult<Suspended> {
+    let run: Suspended = serde_json::from_str(contents)
+        .map_err(|e| GameError::ResourceLoadError(format!("Bad suspend file {}: {}", path, e)))?;
+    if run.version != SUSPEND_VERSION {
+        return Err(GameError::ResourceLoadError(format!("Suspend file {} is from another version", path)));
+    }
+    Ok(run)
+}
+
+/// The profile's suspended run, if it has one that can still be loaded
+pub fn load_suspended(ctx: &mut Context, profile_id: u32) -> Option<Suspended> {
+    let path = suspend_path(profile_id);
+    if !ctx.filesystem.is_file(&path) {
+        return None;
+    }
+    match read_suspended(ctx, &path) {
+        Ok(run) => Some(run),
+        Err(e) => {
+            println!("Could not read the suspended run");
+            println!("Error: {}", e);
+            None
+        }
+    }
+}
+
+pub fn save_suspended(ctx: &mut Context, profile_id: u32, run: &Suspended) -> GameResult<()> {
+    let json = serde_json::to_string(run)
+        .map_err(|e| GameError::UnknownError(format!("Could not write the suspended run: {}", e)))?;
+    if !ctx.filesystem.is_dir(PROFILE_DIR) {
+        ctx.filesystem.create_dir(PROFILE_DIR)?;
+    }
+    let mut file = ctx.filesystem.create(suspend_path(profile_id))?;
+    file.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+pub fn delete_suspended(ctx: &mut Context, profile_id: u32) -> GameResult<()> {
+    let path = suspend_path(profile_id);
+    if ctx.filesystem.is_file(&path) {
+        ctx.filesystem.delete(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use level::Level;
+    use {create_player, create_minion, create_inspector, create_success_five, create_disposal_sites};
+
+    fn example() -> Suspended {
+        let level = Level::default_layout(800, 600);
+        let mut rng = SimRng::new(99);
+        rng.next_u32();
+        let mut minion = create_minion(120.0, 80.0, &Mutators::default());
+        minion.archetype = Archetype::Grumpy;
+        minion.ring = Some(RingParams { speed: 2.0, goal: 0.3 });
+        let mut scoring = Scoring::default();
+        scoring.total = 42;
+        scoring.combo = 4;
+        let mut rounds = Rounds::default();
+        rounds.round = 2;
+        rounds.spent = 10;
+        let mut skill = SkillTracker::default();
+        skill.record(0.02, 0.5, 2.0, 0.1);
+        Suspended {
+            version: SUSPEND_VERSION,
+            state: 11,
+            mode: GameMode::TimeAttack,
+            mutators: Mutators::default(),
+            level_index: 1,
+            player: create_player(300.0, 200.0),
+            minions: vec![minion.clone(), create_minion(10.0, 20.0, &Mutators::default())],
+            dead_minions: vec![minion],
+            sites: create_disposal_sites(&level),
+            inspector: create_inspector(50.0, 60.0),
+            inspector_waypoint: 2,
+            success_five: create_success_five(),
+            carrying: true,
+            witnesses: 3,
+            timer: 12.5,
+            fire_move_timer: 4.25,
+            scoring: scoring,
+            rounds: rounds,
+            difficulty: level.difficulty.at(12),
+            skill: skill,
+            partner: Archetype::Shy,
+            high_five_kind: Some(MinigameKind::Arrows),
+            high_five_ring: Some(RingParams { speed: 1.5, goal: 0.6 }),
+            friends: 17,
+            kills: 2,
+            achievements: AchievementTracker::default(),
+            rng: rng,
+            seed: 1234,
+            spawns: 9,
+            minigames: 18,
+            today: 20745,
+        }
+    }
+
+    #[test]
+    fn rng_carries_on_after_a_round_trip() {
+        let mut rng = SimRng::new(7);
+        for _ in 0..5 {
+            rng.next_u32();
+        }
+        let json = serde_json::to_string(&rng).unwrap();
+        let mut back: SimRng = serde_json::from_str(&json).unwrap();
+        let ahead: Vec<u32> = (0..20).map(|_| rng.next_u32()).collect();
+        let behind: Vec<u32> = (0..20).map(|_| back.next_u32()).collect();
+        assert_eq!(ahead, behind);
+    }
+
+    #[test]
+    fn rng_depends_on_the_seed() {
+        let (mut a, mut b) = (SimRng::new(1), SimRng::new(2));
+        assert!((0..4).map(|_| a.next_u32()).collect::<Vec<u32>>()
+                != (0..4).map(|_| b.next_u32()).collect::<Vec<u32>>());
+        let mut zero = SimRng::new(0);
+        assert!((0..4).any(|_| zero.next_u32() != 0));
+    }
+
+    #[test]
+    fn suspend_file_keeps_every_field() {
+        let run = example();
+        let json = serde_json::to_string(&run).unwrap();
+        let back = parse_suspended(&json, "/profiles/0_suspend.json").unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&run).unwrap());
+        assert_eq!(back.describe(), run.describe());
+    }
+
+    #[test]
+    fn other_versions_are_thrown_away() {
+        let mut run = example();
+        run.version = SUSPEND_VERSION + 1;
+        let json = serde_json::to_string(&run).unwrap();
+        assert!(parse_suspended(&json, "/profiles/0_suspend.json").is_err());
+        assert!(parse_suspended("{", "/profiles/0_suspend.json").is_err());
+    }
+}
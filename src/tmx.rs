@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use ggez::{Context, GameResult, GameError};
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use difficulty::DifficultyCurve;
+use level::{Level, Region, SiteDef, CollisionGrid};
+
+/// ********************************************************************
+/// Tiled Maps
+///
+/// Loads maps saved from the Tiled editor. Tile layers are drawn as the
+/// background, apart from a layer called "collision" whose tiles mark
+/// where nobody can walk. Objects place everything else, going by their
+/// type:
+///
+/// * `spawn`, `exclusion` and `obstacle` rectangles
+/// * `fire_pit`, `bush` and `closet`, with optional `capacity`,
+///   `burn_time` and `find_rate` properties
+/// * a `player_start` point
+/// * `waypoint` points for the inspector, walked in order of their names
+///
+/// The map's own properties can set `name`, `timer`, `starting_minions`
+/// and `friends_goal`. Tile layers have to be saved with CSV or XML
+/// layer encoding.
+///
+/// `example.tmx` in the resources directory uses all of the above.
+/// ********************************************************************
+
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub image: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TileLayer {
+    pub name: String,
+    /// Global tile ids, row by row from the top left. Zero is empty.
+    pub tiles: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapObject {
+    pub name: String,
+    pub kind: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TiledMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<Tileset>,
+    pub layers: Vec<TileLayer>,
+    pub objects: Vec<MapObject>,
+    pub properties: HashMap<String, String>,
+}
+
+/// A tile to draw: which tileset image, where in it, and where on the map
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedTile {
+    pub tileset: usize,
+    /// Source rectangle in the tileset image, in 0-1 texture coordinates
+    pub src: (f32, f32, f32, f32),
+    /// Centre of the tile in world coordinates
+    pub x: f32,
+    pub y: f32,
+}
+
+fn attr(attributes: &[OwnedAttribute], name: &str) -> Option<String> {
+    attributes.iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.clone())
+}
+
+fn attr_num<T: ::std::str::FromStr>(attributes: &[OwnedAttribute], name: &str, default: T) -> T {
+    attr(attributes, name)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn map_error(path: &str, message: String) -> GameError {
+    GameError::ResourceLoadError(format!("Bad map file {}: {}", path, message))
+}
+
+/// Reads a Tiled map. External tilesets are looked up next to the map.
+pub fn load_map(ctx: &mut Context, path: &str) -> GameResult<TiledMap> {
+    let mut file = ctx.filesystem.open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let (mut map, external_tilesets) = parse_map(&contents, path)?;
+
+    let dir = match path.rfind('/') {
+        Some(i) => path[..i + 1].to_string(),
+        None => "/".to_string(),
+    };
+    for (first_gid, source) in external_tilesets {
+        let mut set = load_tileset(ctx, &format!("{}{}", dir, source))?;
+        set.first_gid = first_gid;
+        map.tilesets.push(set);
+    }
+    for set in map.tilesets.iter_mut() {
+        if !set.image.starts_with('/') {
+            set.image = format!("{}{}", dir, set.image);
+        }
+        if set.columns == 0 && set.tile_width != 0 {
+            set.columns = set.image_width / set.tile_width;
+        }
+    }
+    map.tilesets.sort_by_key(|set| set.first_gid);
+
+    Ok(map)
+}
+
+/// Parses the map file itself, handing back the external tilesets it
+/// points at along with their first tile ids. `path` is only for errors.
+fn parse_map(contents: &str, path: &str) -> GameResult<(TiledMap, Vec<(u32, String)>)> {
+    let mut map = TiledMap {
+        width: 0,
+        height: 0,
+        tile_width: 0,
+        tile_height: 0,
+        tilesets: vec![],
+        layers: vec![],
+        objects: vec![],
+        properties: HashMap::new(),
+    };
+
+    let mut layer: Option<TileLayer> = None;
+    let mut encoding = String::new();
+    let mut object: Option<MapObject> = None;
+    let mut tileset: Option<Tileset> = None;
+    let mut external_tilesets = vec![];
+    let mut in_data = false;
+    let mut elements: Vec<String> = vec![];
+
+    for event in EventReader::new(contents.as_bytes()) {
+        let event = event.map_err(|e| map_error(path, e.to_string()))?;
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                elements.push(name.local_name.clone());
+                match name.local_name.as_str() {
+                    "map" => {
+                        map.width = attr_num(&attributes, "width", 0);
+                        map.height = attr_num(&attributes, "height", 0);
+                        map.tile_width = attr_num(&attributes, "tilewidth", 0);
+                        map.tile_height = attr_num(&attributes, "tileheight", 0);
+                    }
+                    "tileset" => {
+                        let first_gid = attr_num(&attributes, "firstgid", 1);
+                        if let Some(source) = attr(&attributes, "source") {
+                            external_tilesets.push((first_gid, source));
+                        }
+                        else {
+                            tileset = Some(Tileset {
+                                first_gid: first_gid,
+                                image: String::new(),
+                                image_width: 0,
+                                image_height: 0,
+                                tile_width: attr_num(&attributes, "tilewidth", map.tile_width),
+                                tile_height: attr_num(&attributes, "tileheight", map.tile_height),
+                                columns: attr_num(&attributes, "columns", 0),
+                            });
+                        }
+                    }
+                    "image" => {
+                        if let Some(ref mut set) = tileset {
+                            set.image = attr(&attributes, "source").unwrap_or_default();
+                            set.image_width = attr_num(&attributes, "width", 0);
+                            set.image_height = attr_num(&attributes, "height", 0);
+                        }
+                    }
+                    "layer" => {
+                        layer = Some(TileLayer {
+                            name: attr(&attributes, "name").unwrap_or_default(),
+                            tiles: vec![],
+                        });
+                    }
+                    "data" => {
+                        encoding = attr(&attributes, "encoding").unwrap_or_default();
+                        if encoding != "" && encoding != "csv" {
+                            return Err(map_error(path, format!("{} layer encoding isn't supported, save the map as CSV", encoding)));
+                        }
+                        in_data = true;
+                    }
+                    "tile" => {
+                        // Tiles written out one by one with the XML encoding
+                        if in_data && encoding == "" {
+                            if let Some(ref mut l) = layer {
+                                l.tiles.push(attr_num(&attributes, "gid", 0));
+                            }
+                        }
+                    }
+                    "object" => {
+                        object = Some(MapObject {
+                            name: attr(&attributes, "name").unwrap_or_default(),
+                            kind: attr(&attributes, "type")
+                                .or_else(|| attr(&attributes, "class"))
+                                .unwrap_or_default(),
+                            x: attr_num(&attributes, "x", 0.0),
+                            y: attr_num(&attributes, "y", 0.0),
+                            width: attr_num(&attributes, "width", 0.0),
+                            height: attr_num(&attributes, "height", 0.0),
+                            properties: HashMap::new(),
+                        });
+                    }
+                    "property" => {
+                        let key = attr(&attributes, "name").unwrap_or_default();
+                        let value = attr(&attributes, "value").unwrap_or_default();
+                        let parent = if elements.len() >= 3 { elements[elements.len() - 3].as_str() } else { "" };
+                        if let Some(ref mut o) = object {
+                            o.properties.insert(key, value);
+                        }
+                        else if parent == "map" {
+                            map.properties.insert(key, value);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            XmlEvent::Characters(text) => {
+                if in_data && encoding == "csv" {
+                    if let Some(ref mut l) = layer {
+                        for gid in text.split(',') {
+                            let gid = gid.trim();
+                            if gid != "" {
+                                l.tiles.push(gid.parse().map_err(|_| map_error(path, format!("bad tile id {}", gid)))?);
+                            }
+                        }
+                    }
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                elements.pop();
+                match name.local_name.as_str() {
+                    "data" => in_data = false,
+                    "layer" => {
+                        if let Some(l) = layer.take() {
+                            map.layers.push(l);
+                        }
+                    }
+                    "object" => {
+                        if let Some(o) = object.take() {
+                            map.objects.push(o);
+                        }
+                    }
+                    "tileset" => {
+                        if let Some(set) = tileset.take() {
+                            map.tilesets.push(set);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if map.width == 0 || map.height == 0 || map.tile_width == 0 || map.tile_height == 0 {
+        return Err(map_error(path, "the map needs a width, height and tile size".to_string()));
+    }
+    Ok((map, external_tilesets))
+}
+
+/// Reads an external `.tsx` tileset
+fn load_tileset(ctx: &mut Context, path: &str) -> GameResult<Tileset> {
+    let mut file = ctx.filesystem.open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let mut set = Tileset {
+        first_gid: 1,
+        image: String::new(),
+        image_width: 0,
+        image_height: 0,
+        tile_width: 0,
+        tile_height: 0,
+        columns: 0,
+    };
+    for event in EventReader::new(contents.as_bytes()) {
+        let event = event.map_err(|e| map_error(path, e.to_string()))?;
+        if let XmlEvent::StartElement { name, attributes, .. } = event {
+            match name.local_name.as_str() {
+                "tileset" => {
+                    set.tile_width = attr_num(&attributes, "tilewidth", 0);
+                    set.tile_height = attr_num(&attributes, "tileheight", 0);
+                    set.columns = attr_num(&attributes, "columns", 0);
+                }
+                "image" => {
+                    set.image = attr(&attributes, "source").unwrap_or_default();
+                    set.image_width = attr_num(&attributes, "width", 0);
+                    set.image_height = attr_num(&attributes, "height", 0);
+                }
+                _ => (),
+            }
+        }
+    }
+    Ok(set)
+}
+
+impl TiledMap {
+    fn pixel_width(&self) -> f32 {
+        (self.width * self.tile_width) as f32
+    }
+
+    fn pixel_height(&self) -> f32 {
+        (self.height * self.tile_height) as f32
+    }
+
+    /// Tiled measures from the top left corner of the map with Y pointing
+    /// down, the map gets centred on the world origin
+    fn to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        (x - self.pixel_width() / 2.0, self.pixel_height() / 2.0 - y)
+    }
+
+    fn object_region(&self, object: &MapObject) -> Region {
+        let (x, y) = self.to_world(object.x, object.y + object.height);
+        Region::new(x, y, object.width, object.height)
+    }
+
+    /// Objects are placed by their top left corner, sites and starts
+    /// want the middle
+    fn object_center(&self, object: &MapObject) -> (f32, f32) {
+        self.to_world(object.x + object.width / 2.0, object.y + object.height / 2.0)
+    }
+
+    fn tileset_for(&self, gid: u32) -> Option<usize> {
+        let mut found = None;
+        for (i, set) in self.tilesets.iter().enumerate() {
+            if set.first_gid <= gid {
+                found = Some(i);
+            }
+        }
+        found
+    }
+
+    /// Every tile on the visible layers, bottom layer first
+    pub fn placed_tiles(&self) -> Vec<PlacedTile> {
+        let mut placed = vec![];
+        for layer in self.layers.iter() {
+            if layer.name == "collision" {
+                continue;
+            }
+            for (i, &raw_gid) in layer.tiles.iter().enumerate() {
+                // The top bits are flip flags
+                let gid = raw_gid & 0x1fffffff;
+                if gid == 0 {
+                    continue;
+                }
+                let set_index = match self.tileset_for(gid) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let set = &self.tilesets[set_index];
+                if set.columns == 0 || set.image_width == 0 || set.image_height == 0 {
+                    continue;
+                }
+                let local = gid - set.first_gid;
+                let u = ((local % set.columns) * set.tile_width) as f32 / set.image_width as f32;
+                let v = ((local / set.columns) * set.tile_height) as f32 / set.image_height as f32;
+                let col = i as u32 % self.width;
+                let row = i as u32 / self.width;
+                let (x, y) = self.to_world((col * self.tile_width) as f32 + self.tile_width as f32 / 2.0,
+                                           (row * self.tile_height) as f32 + self.tile_height as f32 / 2.0);
+                placed.push(PlacedTile {
+                    tileset: set_index,
+                    src: (u, v,
+                          set.tile_width as f32 / set.image_width as f32,
+                          set.tile_height as f32 / set.image_height as f32),
+                    x: x,
+                    y: y,
+                });
+            }
+        }
+        placed
+    }
+
+    /// Builds the level the map describes
+    pub fn to_level(&self) -> Level {
+        let prop = |key: &str| self.properties.get(key).cloned();
+        let mut level = Level {
+            name: prop("name").unwrap_or("Tiled Map".to_string()),
+            background: None,
+            player_start: (0.0, 0.0),
+            spawn_regions: vec![],
+            exclusion_zones: vec![],
+            obstacles: vec![],
+            sites: vec![],
+            inspector_route: vec![],
+            minions: vec![],
+            starting_minions: prop("starting_minions").and_then(|v| v.parse().ok()).unwrap_or(3),
+            timer: prop("timer").and_then(|v| v.parse().ok()).unwrap_or(45.0),
+            friends_goal: prop("friends_goal").and_then(|v| v.parse().ok()),
+            collision: None,
+            difficulty: DifficultyCurve::default(),
+        };
+
+        let mut waypoints = vec![];
+        for object in self.objects.iter() {
+            let num = |key: &str, default: f32| {
+                object.properties.get(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+            };
+            let (x, y) = self.object_center(object);
+            match object.kind.as_str() {
+                "spawn" => level.spawn_regions.push(self.object_region(object)),
+                "exclusion" => level.exclusion_zones.push(self.object_region(object)),
+                "obstacle" => level.obstacles.push(self.object_region(object)),
+                "player_start" => level.player_start = (x, y),
+                "waypoint" => waypoints.push((object.name.clone(), (x, y))),
+                "fire_pit" => level.sites.push(SiteDef::FirePit {
+                    x: x, y: y,
+                    capacity: num("capacity", 2.0) as usize,
+                    burn_time: num("burn_time", 4.0),
+                }),
+                "bush" => level.sites.push(SiteDef::Bush {
+                    x: x, y: y,
+                    capacity: num("capacity", 2.0) as usize,
+                    find_rate: num("find_rate", 0.03),
+                }),
+                "closet" => level.sites.push(SiteDef::Closet {
+                    x: x, y: y,
+                    capacity: num("capacity", 1.0) as usize,
+                    find_rate: num("find_rate", 0.01),
+                }),
+                _ => println!("Ignoring map object {} of unknown type {}", object.name, object.kind),
+            }
+        }
+        waypoints.sort_by(|a, b| a.0.cmp(&b.0));
+        level.inspector_route = waypoints.into_iter().map(|w| w.1).collect();
+
+        for layer in self.layers.iter() {
+            if layer.name == "collision" {
+                let (x, y) = self.to_world(0.0, self.pixel_height());
+                level.collision = Some(CollisionGrid {
+                    x: x,
+                    y: y,
+                    columns: self.width,
+                    rows: self.height,
+                    tile_width: self.tile_width as f32,
+                    tile_height: self.tile_height as f32,
+                    solid: layer.tiles.iter().map(|&gid| gid != 0).collect(),
+                });
+            }
+        }
+
+        // Without any spawn regions there's nowhere to put minions, fall
+        // back on the whole map
+        if level.spawn_regions.len() == 0 {
+            let (x, y) = self.to_world(0.0, self.pixel_height());
+            level.spawn_regions.push(Region::new(x, y, self.pixel_width(), self.pixel_height()));
+        }
+        level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use level::SiteDef;
+
+    const EXAMPLE: &str = include_str!("../example.tmx");
+
+    fn example() -> TiledMap {
+        parse_map(EXAMPLE, "/example.tmx").unwrap().0
+    }
+
+    #[test]
+    fn reads_map_size_and_properties() {
+        let map = example();
+        assert_eq!((map.width, map.height, map.tile_width, map.tile_height), (4, 3, 50, 50));
+        assert_eq!(map.properties.get("name").map(|v| v.as_str()), Some("Example Map"));
+        let level = map.to_level();
+        assert_eq!(level.name, "Example Map");
+        assert_eq!(level.timer, 60.0);
+        assert_eq!(level.starting_minions, 2);
+        assert_eq!(level.friends_goal, None);
+    }
+
+    #[test]
+    fn reads_csv_and_xml_tile_layers() {
+        let map = example();
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[0].name, "ground");
+        assert_eq!(map.layers[0].tiles, (1..13).collect::<Vec<u32>>());
+        assert_eq!(map.layers[1].name, "collision");
+        assert_eq!(map.layers[1].tiles, vec![1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(map.tilesets.len(), 1);
+        assert_eq!(map.tilesets[0].image, "green.png");
+        assert_eq!(map.tilesets[0].columns, 4);
+    }
+
+    #[test]
+    fn places_tiles_from_visible_layers_only() {
+        let tiles = example().placed_tiles();
+        assert_eq!(tiles.len(), 12);
+        // Top left tile, centred on its cell with Y pointing up
+        assert_eq!((tiles[0].x, tiles[0].y), (-75.0, 50.0));
+        assert_eq!(tiles[0].src, (0.0, 0.0, 0.25, 0.25));
+        // Tile 6 is second along, second down the tileset
+        assert_eq!(tiles[5].src, (0.25, 0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn collision_layer_becomes_a_grid() {
+        let level = example().to_level();
+        let grid = level.collision.expect("collision grid");
+        assert_eq!((grid.x, grid.y, grid.columns, grid.rows), (-100.0, -75.0, 4, 3));
+        assert_eq!(grid.solid[0], true);
+        assert_eq!(grid.solid[1], false);
+        assert_eq!(grid.solid[3], true);
+    }
+
+    #[test]
+    fn objects_place_by_type() {
+        let level = example().to_level();
+        assert_eq!(level.player_start, (0.0, 0.0));
+        assert_eq!(level.spawn_regions.len(), 1);
+        let spawn = level.spawn_regions[0];
+        assert_eq!((spawn.x, spawn.y, spawn.w, spawn.h), (-100.0, -75.0, 200.0, 100.0));
+        // Waypoints are walked in order of their names
+        assert_eq!(level.inspector_route, vec![(-50.0, -50.0), (50.0, -50.0)]);
+        // The statue isn't anything the game knows, so it's left out
+        assert_eq!(level.sites.len(), 3);
+        match level.sites[0] {
+            SiteDef::FirePit { x, y, capacity, burn_time } => {
+                assert_eq!((x, y, capacity, burn_time), (0.0, -25.0, 3, 2.5));
+            }
+            ref other => panic!("expected a fire pit, got {:?}", other),
+        }
+        match level.sites[1] {
+            SiteDef::Bush { x, y, capacity, .. } => assert_eq!((x, y, capacity), (-80.0, 5.0, 2)),
+            ref other => panic!("expected a bush, got {:?}", other),
+        }
+        // Newer Tiled versions write the type as a class
+        match level.sites[2] {
+            SiteDef::Closet { x, y, capacity, .. } => assert_eq!((x, y, capacity), (80.0, 5.0, 1)),
+            ref other => panic!("expected a closet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn whole_map_spawns_without_spawn_regions() {
+        let contents = r#"<map width="2" height="2" tilewidth="10" tileheight="10">
+            <objectgroup><object type="player_start" x="10" y="10"/></objectgroup>
+        </map>"#;
+        let level = parse_map(contents, "/empty.tmx").unwrap().0.to_level();
+        assert_eq!(level.spawn_regions.len(), 1);
+        let spawn = level.spawn_regions[0];
+        assert_eq!((spawn.x, spawn.y, spawn.w, spawn.h), (-10.0, -10.0, 20.0, 20.0));
+        assert_eq!(level.name, "Tiled Map");
+        assert_eq!(level.starting_minions, 3);
+    }
+
+    #[test]
+    fn external_tilesets_are_handed_back() {
+        let contents = r#"<map width="1" height="1" tilewidth="10" tileheight="10">
+            <tileset firstgid="5" source="tiles.tsx"/>
+        </map>"#;
+        let (map, external) = parse_map(contents, "/maps/a.tmx").unwrap();
+        assert_eq!(map.tilesets.len(), 0);
+        assert_eq!(external, vec![(5, "tiles.tsx".to_string())]);
+    }
+
+    #[test]
+    fn unsupported_encodings_are_refused() {
+        let contents = r#"<map width="1" height="1" tilewidth="10" tileheight="10">
+            <layer name="ground"><data encoding="base64">AQAAAA==</data></layer>
+        </map>"#;
+        assert!(parse_map(contents, "/a.tmx").is_err());
+        assert!(parse_map("<map><layer>", "/a.tmx").is_err());
+    }
+
+    #[test]
+    fn maps_without_a_size_are_refused() {
+        let map = |width, height, tile_width, tile_height| format!(
+            r#"<map width="{}" height="{}" tilewidth="{}" tileheight="{}">
+                <layer name="ground"><data encoding="csv">1</data></layer>
+            </map>"#, width, height, tile_width, tile_height);
+        assert!(parse_map(&map(1, 1, 10, 10), "/a.tmx").is_ok());
+        assert!(parse_map(&map(0, 1, 10, 10), "/a.tmx").is_err());
+        assert!(parse_map(&map(1, 0, 10, 10), "/a.tmx").is_err());
+        assert!(parse_map(&map(1, 1, 0, 10), "/a.tmx").is_err());
+        assert!(parse_map(&map(1, 1, 10, 0), "/a.tmx").is_err());
+        let unsized_map = r#"<map><layer name="ground"><data encoding="csv">1</data></layer></map>"#;
+        assert!(parse_map(unsized_map, "/a.tmx").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use ggez::{Context, GameResult};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawMode, Point};
+use na;
+use na::geometry::Point2;
+
+use archetype::Archetype;
+use level::RingParams;
+use minigame::MinigameKind;
+use mutators::Mutators;
+use settings::Action;
+use {MainState, SiteKind, add_minion, draw_text, start_run};
+
+/// ********************************************************************
+/// Tutorial
+///
+/// Played on the run's own level the first time a profile starts a
+/// run, before the run itself. Each step waits until the player has
+/// actually done what it asks. There's only ever the one minion about,
+/// nothing new spawns, the clock and the inspector stand still, and the
+/// run's mutators are held back until it's over. None of it counts
+/// towards the run or the profile: no achievements are earned, the
+/// skill tracker isn't fed, and its minions don't use up the run's
+/// spawns.
+///
+/// Tab skips it. Finishing or skipping it marks it done on the profile.
+/// ********************************************************************
+
+/// Pixels to walk before the first step counts
+const TUTORIAL_WALK: f32 = 150.0;
+/// Shrink speed of the tutorial's ring, well under anything in a run
+const TUTORIAL_RING: RingParams = RingParams { speed: 0.15, goal: 0.5 };
+/// Seconds the last prompt stays up before the run starts
+const TUTORIAL_DONE_TIME: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TutorialStep {
+    Move,
+    Approach,
+    HighFive,
+    Miss,
+    PickUp,
+    Burn,
+    Done,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tutorial {
+    pub step: TutorialStep,
+    /// Where the lord started walking from
+    start: Point2<f32>,
+    /// The run's mutators, put back when the tutorial is over
+    mutators: Mutators,
+    done_time: f32,
+    pub skipped: bool,
+}
+
+/// Starts the tutorial over a freshly started run
+pub fn start_tutorial(game: &mut MainState) {
+    game.tutorial = Some(Tutorial {
+        step: TutorialStep::Move,
+        start: game.player.pos,
+        mutators: game.mutators,
+        done_time: TUTORIAL_DONE_TIME,
+        skipped: false,
+    });
+    game.mutators = Mutators::default();
+    game.minions.clear();
+    game.state = 0;
+}
+
+/// Stops the tutorial, if it's going, putting the run's mutators back
+pub fn end_tutorial(game: &mut MainState) {
+    if let Some(tutorial) = game.tutorial.take() {
+        game.mutators = tutorial.mutators;
+    }
+}
+
+/// Whether a missed high five leaves a body. In the tutorial only the
+/// miss it asks for does.
+pub fn miss_kills(game: &MainState) -> bool {
+    match game.tutorial {
+        Some(ref tutorial) => tutorial.step == TutorialStep::Miss,
+        None => game.mode.deaths(),
+    }
+}
+
+/// A minion with the slow ring, somewhere free
+fn add_tutorial_minion(game: &mut MainState) {
+    if add_minion(game) {
+        if let Some(minion) = game.minions.last_mut() {
+            minion.archetype = Archetype::Regular;
+            minion.ring = Some(TUTORIAL_RING);
+            minion.minigame = Some(MinigameKind::Ring);
+        }
+    }
+}
+
+/// The first key bound to an action, for the prompts
+fn key_for(game: &MainState, action: Action) -> String {
+    match game.profile.bindings.keys(action).first() {
+        Some(key) => key.clone(),
+        None => "(unbound)".to_string(),
+    }
+}
+
+fn prompt(game: &MainState, step: TutorialStep) -> String {
+    match step {
+        TutorialStep::Move => format!("Walk around with {}, {}, {} and {}",
+                                      key_for(game, Action::Up), key_for(game, Action::Left),
+                                      key_for(game, Action::Down), key_for(game, Action::Right)),
+        TutorialStep::Approach => "Walk up to the minion until it notices you".to_string(),
+        TutorialStep::HighFive => format!("Press {} to high five, and again when the rings line up",
+                                          key_for(game, Action::Fire)),
+        TutorialStep::Miss => "Now miss one on purpose, and see what happens".to_string(),
+        TutorialStep::PickUp => format!("Nobody can see that. Pick the body up with {}",
+                                        key_for(game, Action::PickUp)),
+        TutorialStep::Burn => format!("Carry it to the fire and drop it in with {}",
+                                      key_for(game, Action::Drop)),
+        TutorialStep::Done => "That's all there is to it. Good luck!".to_string(),
+    }
+}
+
+/// Whether the player has done what the step asks
+fn step_done(game: &MainState, tutorial: &Tutorial) -> bool {
+    match tutorial.step {
+        TutorialStep::Move => na::distance(&game.player.pos, &tutorial.start) > TUTORIAL_WALK,
+        TutorialStep::Approach => game.attention.life == 1.0,
+        TutorialStep::HighFive => game.friends > 0,
+        TutorialStep::Miss => game.dead_minions.len() > 0,
+        TutorialStep::PickUp => game.carrying,
+        // Levels without a fire pit don't hold the tutorial up
+        TutorialStep::Burn => {
+            game.sites.iter().all(|site| site.kind != SiteKind::FirePit)
+            || game.sites.iter().any(|site| site.kind == SiteKind::FirePit && site.bodies.len() > 0)
+        }
+        TutorialStep::Done => tutorial.done_time <= 0.0,
+    }
+}
+
+fn next_step(step: TutorialStep) -> TutorialStep {
+    match step {
+        TutorialStep::Move => TutorialStep::Approach,
+        TutorialStep::Approach => TutorialStep::HighFive,
+        TutorialStep::HighFive => TutorialStep::Miss,
+        TutorialStep::Miss => TutorialStep::PickUp,
+        TutorialStep::PickUp => TutorialStep::Burn,
+        TutorialStep::Burn | TutorialStep::Done => TutorialStep::Done,
+    }
+}
+
+/// Moves the tutorial along, and starts the run proper once it's done.
+/// Starting the run ends the tutorial.
+pub fn tutorial_update(game: &mut MainState, ctx: &mut Context, dt: f32) -> GameResult<()> {
+    let mut tutorial = match game.tutorial.take() {
+        Some(tutorial) => tutorial,
+        None => return Ok(()),
+    };
+    if tutorial.step == TutorialStep::Done {
+        tutorial.done_time -= dt;
+    }
+    if tutorial.skipped || (tutorial.step == TutorialStep::Done && step_done(game, &tutorial)) {
+        game.tutorial = Some(tutorial);
+        game.profile.tutorial_done = true;
+        game.profile_dirty = true;
+        let index = game.level_index;
+        start_run(game, ctx, index)?;
+        game.state = 0;
+        return Ok(());
+    }
+    // Only step forward on the floor, not partway through a high five
+    if game.state == 0 && step_done(game, &tutorial) {
+        tutorial.step = next_step(tutorial.step);
+    }
+    // A minion for every step that needs one, including another go if
+    // the first high five was missed
+    let wants_minion = match tutorial.step {
+        TutorialStep::Move | TutorialStep::Approach | TutorialStep::HighFive | TutorialStep::Miss => true,
+        _ => false,
+    };
+    if game.state == 0 && wants_minion && game.minions.len() == 0 {
+        add_tutorial_minion(game);
+    }
+    game.tutorial = Some(tutorial);
+    Ok(())
+}
+
+pub fn draw_tutorial(game: &mut MainState, ctx: &mut Context) -> GameResult<()> {
+    let step = match game.tutorial {
+        Some(ref tutorial) => tutorial.step,
+        None => return Ok(()),
+    };
+    let font = &mut graphics::Font::new(ctx, "/OpenSans-Regular.ttf", 18)?;
+    let lines = [prompt(game, step), "Tab Skips The Tutorial".to_string()];
+    for x in 0..lines.len() {
+        let mut line_text = graphics::Text::new(ctx, &lines[x], font)?;
+        let line_dest = Point::new((game.screen_width / 2) as f32,
+                                   game.screen_height as f32 - 110.0 + 40.0 * x as f32);
+        graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 0.9))?;
+        graphics::rectangle(ctx, DrawMode::Fill,
+                            graphics::Rect::new(line_dest.x, line_dest.y,
+                                                line_text.width() as f32 + 30.0, 34.0))?;
+        graphics::set_color(ctx, graphics::BLACK)?;
+        draw_text(ctx, &mut line_text, line_dest)?;
+    }
+    graphics::set_color(ctx, graphics::WHITE)?;
+    Ok(())
+}